@@ -1,9 +1,15 @@
+use std::env;
 use std::io;
+use std::path::PathBuf;
 
 fn main() -> Result<(), io::Error> {
+    let out_dir: PathBuf = env::var_os("OUT_DIR")
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::other("OUT_DIR missing"))?;
     tonic_build::configure()
         .build_server(true)
         .build_client(false)
+        .file_descriptor_set_path(out_dir.join("memdatabase_descriptor.bin"))
         .compile(
             &[
                 "memdatabase/v1/dget.proto",
@@ -0,0 +1,74 @@
+#!/bin/sh
+
+which grpcurl | fgrep -q grpcurl || exec sh -c 'echo grpcurl missing.; exit 1'
+which jaq | fgrep -q jaq || exec sh -c 'echo jaq missing.; exit 1'
+which base64 | fgrep -q base64 || exec sh -c 'echo base64 missing.; exit 1'
+
+server=localhost:50052
+value=$(printf '%0900d' 0)
+
+trap 'kill ${pid} 2>/dev/null' EXIT
+
+# Starts the server which keeps about ten of the keys set below.
+start() {
+	policy=$1
+
+	kill ${pid} 2>/dev/null
+	wait ${pid} 2>/dev/null
+
+	ENV_LISTEN_ADDR=127.0.0.1:50052 \
+		ENV_MAX_MEMORY=10000 \
+		ENV_EVICTION_POLICY="${policy}" \
+		./target/release/memdatabase &
+	pid=$!
+	sleep 1
+}
+
+set_key() {
+	key=$1
+
+	jaq \
+		-c \
+		--arg key "$(echo -n "key${key}" | base64)" \
+		--arg value "${value}" \
+		-n '{ key: $key, value: $value }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Set
+}
+
+dbsize() {
+	grpcurl \
+		-plaintext \
+		-d '{}' \
+		"${server}" \
+		memdatabase.v1.MemoryDatabaseService/DbSize
+}
+
+echo rejected if full
+start noeviction
+i=0
+while set_key ${i} >/dev/null 2>&1; do
+	i=$((i + 1))
+	test ${i} -lt 100 || exit 1
+done
+set_key ${i} 2>&1 | fgrep -q ResourceExhausted || exit 1
+
+echo evicted if full
+start allkeys-lru
+for i in $(seq 100); do
+	set_key ${i} >/dev/null || exit 1
+done
+dbsize | jaq -e '.count | tonumber < 20' || exit 1
+
+echo volatile-ttl rejected
+kill ${pid}
+wait ${pid}
+ENV_LISTEN_ADDR=127.0.0.1:50052 \
+	ENV_MAX_MEMORY=10000 \
+	ENV_EVICTION_POLICY=volatile-ttl \
+	timeout 5 ./target/release/memdatabase &&
+	exit 1
+test $? -ne 124 || exit 1
@@ -0,0 +1,59 @@
+#!/bin/sh
+
+which otelcol | fgrep -q otelcol || exec sh -c 'echo otelcol missing.; exit 1'
+which grpcurl | fgrep -q grpcurl || exec sh -c 'echo grpcurl missing.; exit 1'
+
+server=localhost:50054
+receiver=127.0.0.1:54317
+workdir=$(mktemp -d)
+
+trace_id=4bf92f3577b34da6a3ce929d0e0e4736
+parent_id=00f067aa0ba902b7
+
+trap 'kill ${pid} ${collector} 2>/dev/null; rm -rf "${workdir}"' EXIT
+
+# The stand-in receiver which logs every span received.
+cat >"${workdir}/otelcol.yaml" <<EOF
+receivers:
+  otlp:
+    protocols:
+      grpc:
+        endpoint: ${receiver}
+exporters:
+  debug:
+    verbosity: detailed
+service:
+  pipelines:
+    traces:
+      receivers: [otlp]
+      exporters: [debug]
+EOF
+
+otelcol --config "${workdir}/otelcol.yaml" 2>"${workdir}/otelcol.log" &
+collector=$!
+sleep 1
+
+ENV_LISTEN_ADDR=127.0.0.1:50054 \
+	ENV_OTLP_ENDPOINT="http://${receiver}" \
+	./target/release/memdatabase &
+pid=$!
+sleep 1
+
+echo with the trace context
+grpcurl \
+	-plaintext \
+	-H "traceparent: 00-${trace_id}-${parent_id}-01" \
+	-d '{}' \
+	"${server}" \
+	memdatabase.v1.MemoryDatabaseService/DbSize ||
+	exit 1
+
+# The spans are exported on shutdown.
+kill -TERM ${pid}
+wait ${pid}
+sleep 1
+
+echo spans received
+fgrep -q "Trace ID       : ${trace_id}" "${workdir}/otelcol.log" || exit 1
+fgrep -q "Parent ID      : ${parent_id}" "${workdir}/otelcol.log" || exit 1
+fgrep -q "Name           : DbSize" "${workdir}/otelcol.log" || exit 1
@@ -0,0 +1,51 @@
+#!/bin/sh
+
+which grpcurl | fgrep -q grpcurl || exec sh -c 'echo grpcurl missing.; exit 1'
+which jaq | fgrep -q jaq || exec sh -c 'echo jaq missing.; exit 1'
+which base64 | fgrep -q base64 || exec sh -c 'echo base64 missing.; exit 1'
+
+server=localhost:50055
+dir=$(mktemp -d)
+
+trap 'kill ${pid} ${watch} ${stream} 2>/dev/null; rm -rf "${dir}"' EXIT
+
+ENV_LISTEN_ADDR=127.0.0.1:50055 ./target/release/memdatabase &
+pid=$!
+sleep 1
+
+grpcurl \
+	-plaintext \
+	-d '{ "service": "" }' \
+	"${server}" \
+	grpc.health.v1.Health/Watch >"${dir}/watch" 2>&1 &
+watch=$!
+
+# Keeps the Execute stream open after the first op.
+(
+	jaq \
+		-c \
+		--arg key "$(echo -n queue4567 | base64)" \
+		-n '{ id: 1, push: { key: $key, value: "wwww" } }'
+	sleep 30
+) |
+	grpcurl \
+		-plaintext \
+		-d @ \
+		"${server}" \
+		memdatabase.v1.MemoryDatabaseService/Execute >"${dir}/execute" 2>&1 &
+stream=$!
+sleep 1
+
+echo stream ends on SIGTERM
+kill -TERM ${pid}
+wait ${pid} || exit 1
+
+i=0
+while kill -0 ${stream} 2>/dev/null; do
+	i=$((i + 1))
+	test ${i} -lt 50 || exit 1
+	sleep 0.1
+done
+
+echo health not serving
+fgrep -q NOT_SERVING "${dir}/watch" || exit 1
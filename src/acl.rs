@@ -0,0 +1,207 @@
+use core::ops::Bound;
+
+use tracing::warn;
+
+use tonic::Status;
+
+use crate::auth::Principal;
+use crate::pattern::prefix_end;
+
+/// Matches any command, any key or any namespace.
+pub const ANY: &str = "*";
+
+/// The keys a request may access.
+pub enum Access {
+    Keys(Vec<Vec<u8>>),
+    Range(Bound<Vec<u8>>, Bound<Vec<u8>>),
+    /// Any key.
+    All,
+}
+
+/// Allows the principal the commands over the keys starting with the prefixes in the namespaces.
+pub struct Rule {
+    pub principal: String,
+    /// The command names; `*` allows any command.
+    pub commands: Vec<String>,
+    /// The key prefixes; the empty prefix allows any key.
+    pub prefixes: Vec<Vec<u8>>,
+    /// The namespace names; `*` allows any namespace and the empty name is the default namespace.
+    pub namespaces: Vec<String>,
+}
+
+impl Rule {
+    pub fn allows(&self, principal: &Principal, ns: &str, command: &str) -> bool {
+        self.principal == principal.name
+            && self.commands.iter().any(|c| c == ANY || c == command)
+            && self.namespaces.iter().any(|n| n == ANY || n == ns)
+    }
+
+    fn covers_key(&self, key: &[u8]) -> bool {
+        self.prefixes.iter().any(|p| key.starts_with(p))
+    }
+
+    /// Checks every key in the range starts with one of the prefixes.
+    fn covers_range(&self, lower: &Bound<Vec<u8>>, upper: &Bound<Vec<u8>>) -> bool {
+        self.prefixes.iter().any(|p| {
+            let lower_ok: bool = match lower {
+                Bound::Included(l) | Bound::Excluded(l) => p <= l,
+                Bound::Unbounded => p.is_empty(),
+            };
+            let upper_ok: bool = match (upper, prefix_end(p)) {
+                (_, Bound::Unbounded) => true,
+                (Bound::Unbounded, _) => false,
+                (Bound::Excluded(u), Bound::Excluded(e)) => *u <= e,
+                (Bound::Included(u), Bound::Excluded(e)) => *u < e,
+                (_, Bound::Included(_)) => false,
+            };
+            lower_ok && upper_ok
+        })
+    }
+}
+
+/// The rules checked before the requests sent to the actor; denies unless a rule allows.
+#[derive(Default)]
+pub struct Acl {
+    /// Every request is allowed if empty.
+    rules: Vec<Rule>,
+}
+
+/// Parses the lines of `principal commands prefixes [namespaces]`; the lists are separated by commas.
+///
+/// The rule applies to any namespace if the namespaces omitted; e.g. `,jobs` names the default
+/// namespace and `jobs`.
+pub fn rules_parse(s: &str) -> Result<Vec<Rule>, Status> {
+    s.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (principal, commands, prefixes, namespaces) = match fields[..] {
+                [principal, commands, prefixes] => Ok((principal, commands, prefixes, ANY)),
+                [principal, commands, prefixes, namespaces] => {
+                    Ok((principal, commands, prefixes, namespaces))
+                }
+                _ => Err(Status::invalid_argument(format!("invalid rule: {line}"))),
+            }?;
+            Ok(Rule {
+                principal: principal.into(),
+                commands: commands.split(',').map(String::from).collect(),
+                prefixes: prefixes
+                    .split(',')
+                    .map(|p| match p {
+                        ANY => vec![],
+                        _ => p.as_bytes().to_vec(),
+                    })
+                    .collect(),
+                namespaces: namespaces.split(',').map(String::from).collect(),
+            })
+        })
+        .collect()
+}
+
+impl Acl {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.rules.is_empty()
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Checks the principal is allowed the command over the keys in the namespace.
+    pub fn check(
+        &self,
+        principal: &Principal,
+        ns: &str,
+        command: &str,
+        access: &Access,
+    ) -> Result<(), Status> {
+        let mut rules = self
+            .rules
+            .iter()
+            .filter(|r| r.allows(principal, ns, command));
+        let allowed: bool = match access {
+            Access::Keys(keys) => {
+                let rules: Vec<&Rule> = rules.collect();
+                !rules.is_empty() && keys.iter().all(|k| rules.iter().any(|r| r.covers_key(k)))
+            }
+            Access::Range(lower, upper) => rules.any(|r| r.covers_range(lower, upper)),
+            Access::All => rules.any(|r| r.prefixes.iter().any(|p| p.is_empty())),
+        };
+        match allowed {
+            true => Ok(()),
+            false => {
+                warn!("permission denied: {} {command} in {ns:?}", principal.name);
+                Err(Status::permission_denied(format!(
+                    "{command} not allowed for {} in {ns:?}",
+                    principal.name
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(prefixes: &[&[u8]]) -> Rule {
+        Rule {
+            principal: "p".into(),
+            commands: vec![ANY.into()],
+            prefixes: prefixes.iter().map(|p| p.to_vec()).collect(),
+            namespaces: vec![ANY.into()],
+        }
+    }
+
+    fn inc(k: &[u8]) -> Bound<Vec<u8>> {
+        Bound::Included(k.to_vec())
+    }
+
+    fn exc(k: &[u8]) -> Bound<Vec<u8>> {
+        Bound::Excluded(k.to_vec())
+    }
+
+    #[test]
+    fn covers_range_within_prefix() {
+        let r: Rule = rule(&[b"ab"]);
+        assert!(r.covers_range(&inc(b"ab"), &exc(b"ac")));
+        assert!(r.covers_range(&exc(b"ab"), &inc(b"ab\xff")));
+        assert!(r.covers_range(&inc(b"ab1"), &inc(b"ab9")));
+        assert!(!r.covers_range(&inc(b"ab"), &inc(b"ac")));
+        assert!(!r.covers_range(&inc(b"aa"), &exc(b"ac")));
+        assert!(!r.covers_range(&inc(b"ab"), &Bound::Unbounded));
+        assert!(!r.covers_range(&Bound::Unbounded, &exc(b"ac")));
+    }
+
+    #[test]
+    fn covers_range_unbounded_prefixes() {
+        assert!(rule(&[b""]).covers_range(&Bound::Unbounded, &Bound::Unbounded));
+        // Every key after the prefix of 0xff bytes starts with it.
+        let r: Rule = rule(&[b"\xff"]);
+        assert!(r.covers_range(&inc(b"\xff"), &Bound::Unbounded));
+        assert!(!r.covers_range(&inc(b"\xfe"), &Bound::Unbounded));
+        let r: Rule = rule(&[b"x", b"ab"]);
+        assert!(r.covers_range(&inc(b"ab"), &exc(b"ac")));
+    }
+
+    #[test]
+    fn check_scopes_namespaces() {
+        let rules: Vec<Rule> = rules_parse("# comment\np Get,Set ab ,jobs\nq * *").unwrap();
+        let acl = Acl::new(rules);
+        let p = Principal { name: "p".into() };
+        let q = Principal { name: "q".into() };
+        let keys = || Access::Keys(vec![b"ab1".to_vec()]);
+        assert!(acl.check(&p, "", "Get", &keys()).is_ok());
+        assert!(acl.check(&p, "jobs", "Set", &keys()).is_ok());
+        assert!(acl.check(&p, "other", "Get", &keys()).is_err());
+        assert!(acl.check(&p, "", "Del", &keys()).is_err());
+        assert!(acl.check(&p, "", "Get", &Access::All).is_err());
+        assert!(acl.check(&q, "other", "FlushDb", &Access::All).is_ok());
+        assert!(rules_parse("p Get").is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use tracing::warn;
+
+use tonic::metadata::MetadataMap;
+use tonic::service::Interceptor;
+use tonic::{Request, Status};
+
+/// The principal of the requests when no credential is configured.
+pub const PRINCIPAL_ANONYMOUS: &str = "anonymous";
+
+pub const API_KEY_KEY: &str = "x-api-key";
+
+/// The authenticated caller; set to the extensions of the request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+}
+
+impl Principal {
+    pub fn anonymous() -> Self {
+        Self {
+            name: PRINCIPAL_ANONYMOUS.into(),
+        }
+    }
+}
+
+pub struct Credential {
+    pub principal: Principal,
+    pub token: String,
+}
+
+/// Authenticates the requests by the bearer tokens or the api keys.
+#[derive(Clone, Default)]
+pub struct Auth {
+    /// Every request is anonymous if empty.
+    creds: Arc<Vec<Credential>>,
+}
+
+/// Compares the bytes in constant time for the same length.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    let diff: u8 = a.iter().zip(b).fold(0, |d, (x, y)| d | (x ^ y));
+    a.len() == b.len() && 0 == diff
+}
+
+/// Gets the token from `authorization: Bearer <token>` or `x-api-key: <token>`.
+pub fn token_get(meta: &MetadataMap) -> Option<&str> {
+    let bearer: Option<&str> = meta
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| {
+            v.strip_prefix("Bearer ")
+                .or_else(|| v.strip_prefix("bearer "))
+        });
+    bearer.or_else(|| meta.get(API_KEY_KEY).and_then(|v| v.to_str().ok()))
+}
+
+/// Parses the `principal:token` entries separated by the separator; skips the entries start with `#`.
+pub fn credentials_parse(s: &str, sep: char) -> Result<Vec<Credential>, Status> {
+    s.split(sep)
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+        .map(|entry| {
+            let (name, token) = entry
+                .split_once(':')
+                .ok_or_else(|| Status::invalid_argument("no token specified"))?;
+            match name.is_empty() || token.is_empty() {
+                true => Err(Status::invalid_argument("empty principal or token")),
+                false => Ok(Credential {
+                    principal: Principal { name: name.into() },
+                    token: token.into(),
+                }),
+            }
+        })
+        .collect()
+}
+
+impl Auth {
+    pub fn new(creds: Vec<Credential>) -> Self {
+        Self {
+            creds: Arc::new(creds),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.creds.is_empty()
+    }
+
+    pub fn authenticate(&self, meta: &MetadataMap) -> Result<Principal, Status> {
+        if !self.is_enabled() {
+            return Ok(Principal::anonymous());
+        }
+        let token: &str =
+            token_get(meta).ok_or_else(|| Status::unauthenticated("no token specified"))?;
+
+        // Checks all credentials so that the time does not depend on which one matched.
+        let found: Option<&Credential> = self.creds.iter().fold(None, |found, c| {
+            match ct_eq(c.token.as_bytes(), token.as_bytes()) {
+                true => Some(c),
+                false => found,
+            }
+        });
+        found
+            .map(|c| c.principal.clone())
+            .ok_or_else(|| Status::unauthenticated("invalid token"))
+    }
+}
+
+impl Interceptor for Auth {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let principal: Principal = self
+            .authenticate(request.metadata())
+            .inspect_err(|e| warn!("authentication failed: {}", e.message()))?;
+        request.extensions_mut().insert(principal);
+        Ok(request)
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::Status;
+
+use crate::lock::Locks;
+use crate::memdatabase::v1::ValType;
+use crate::memory::Memory;
+use crate::slowlog::SlowLog;
+use crate::value::btree::Val;
+
+/// The metadata key to select the namespace; the default namespace is the empty name.
+pub const NAMESPACE_KEY: &str = "x-memdatabase-namespace";
+
+/// The keys and the locks of a namespace.
+#[derive(Default)]
+pub struct Db {
+    pub kv: BTreeMap<Vec<u8>, Val>,
+    pub locks: Locks,
+}
+
+impl Db {
+    pub fn is_empty(&self) -> bool {
+        self.kv.is_empty() && self.locks.is_empty()
+    }
+}
+
+/// The statistics over all namespaces.
+#[derive(Default)]
+pub struct Stats {
+    pub vars: u64,
+    pub maps: u64,
+    pub sets: u64,
+    pub deqs: u64,
+    /// The elements of the maps, the sets and the deques.
+    pub elements: u64,
+    /// The approximate bytes of the keys and the values.
+    pub memory: u64,
+    pub evicted: u64,
+    /// The leases expired.
+    pub expired: u64,
+    /// The requests handled by the command.
+    pub commands: BTreeMap<&'static str, u64>,
+}
+
+/// Keeps the last fencing token of the namespace dropped.
+fn token_keep(tokens: &mut BTreeMap<String, u64>, ns: &str, locks: &Locks) {
+    if 0 < locks.last_token() {
+        tokens.insert(ns.into(), locks.last_token());
+    }
+}
+
+/// The namespaces isolated from each other; an empty namespace is dropped.
+#[derive(Default)]
+pub struct Dbs {
+    dbs: BTreeMap<String, Db>,
+    evicted: u64,
+    /// The leases expired in the dropped namespaces.
+    expired: u64,
+    /// The last fencing tokens of the dropped namespaces; kept to keep the tokens increasing.
+    tokens: BTreeMap<String, u64>,
+    /// The commands handled slower than the threshold in any namespace.
+    slowlog: SlowLog,
+    commands: BTreeMap<&'static str, u64>,
+    /// True after the waiters failed for the shutdown.
+    closed: bool,
+}
+
+impl Dbs {
+    pub fn get(&self, ns: &str) -> Option<&Db> {
+        self.dbs.get(ns)
+    }
+
+    /// Gets the namespace; creates it if missing.
+    pub fn get_mut(&mut self, ns: &str) -> &mut Db {
+        let tokens: &mut BTreeMap<String, u64> = &mut self.tokens;
+        let closed: bool = self.closed;
+        self.dbs.entry(ns.into()).or_insert_with(|| {
+            let mut locks: Locks = Locks::resumed(tokens.remove(ns).unwrap_or(0));
+            if closed {
+                locks.close();
+            }
+            Db {
+                kv: BTreeMap::new(),
+                locks,
+            }
+        })
+    }
+
+    pub fn cleanup(&mut self, ns: &str) {
+        let empty: bool = self.dbs.get(ns).map(Db::is_empty).unwrap_or(false);
+        if empty {
+            if let Some(db) = self.dbs.remove(ns) {
+                self.expired += db.locks.expired();
+                token_keep(&mut self.tokens, ns, &db.locks);
+            }
+        }
+    }
+
+    /// Removes the key to free the memory.
+    pub fn evict(&mut self, ns: &str, key: &[u8]) {
+        self.get_mut(ns).kv.remove(key);
+        self.cleanup(ns);
+        self.evicted += 1;
+    }
+
+    /// Expires the leases and the waiters in all namespaces.
+    pub fn expire(&mut self, now: Instant) {
+        let expired: &mut u64 = &mut self.expired;
+        let tokens: &mut BTreeMap<String, u64> = &mut self.tokens;
+        self.dbs.retain(|ns, db| {
+            db.locks.expire(now);
+            let empty: bool = db.is_empty();
+            if empty {
+                *expired += db.locks.expired();
+                token_keep(tokens, ns, &db.locks);
+            }
+            !empty
+        });
+    }
+
+    /// Fails the lock waiters in every namespace; later contenders fail instead of waiting.
+    pub fn close(&mut self) {
+        self.closed = true;
+        let tokens: &mut BTreeMap<String, u64> = &mut self.tokens;
+        let expired: &mut u64 = &mut self.expired;
+        self.dbs.retain(|ns, db| {
+            db.locks.close();
+            let empty: bool = db.is_empty();
+            if empty {
+                *expired += db.locks.expired();
+                token_keep(tokens, ns, &db.locks);
+            }
+            !empty
+        });
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Gets the counts of the keys tracked by the memory; no key is scanned.
+    pub fn stats(&self, memory: &Memory) -> Stats {
+        let expired: u64 = self.dbs.values().map(|db| db.locks.expired()).sum();
+        Stats {
+            vars: memory.keys(ValType::Var) as u64,
+            maps: memory.keys(ValType::Map) as u64,
+            sets: memory.keys(ValType::Set) as u64,
+            deqs: memory.keys(ValType::Deq) as u64,
+            elements: memory.elements() as u64,
+            memory: memory.used() as u64,
+            evicted: self.evicted,
+            expired: self.expired + expired,
+            commands: self.commands.clone(),
+        }
+    }
+
+    /// Counts the request handled.
+    pub fn count(&mut self, command: &'static str) {
+        *self.commands.entry(command).or_default() += 1;
+    }
+
+    pub fn slowlog(&self) -> &SlowLog {
+        &self.slowlog
+    }
+
+    pub fn slowlog_mut(&mut self) -> &mut SlowLog {
+        &mut self.slowlog
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.dbs
+            .values()
+            .filter_map(|db| db.locks.next_deadline())
+            .min()
+    }
+}
+
+/// Gets the namespace selected by the metadata.
+pub fn namespace_get(meta: &MetadataMap) -> Result<String, Status> {
+    let ov: Option<&MetadataValue<_>> = meta.get(NAMESPACE_KEY);
+    match ov {
+        None => Ok(String::new()),
+        Some(v) => v
+            .to_str()
+            .map(String::from)
+            .map_err(|e| Status::invalid_argument(format!("invalid namespace: {e}"))),
+    }
+}
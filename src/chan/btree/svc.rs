@@ -1,53 +1,159 @@
 use core::cmp::Ordering;
-use core::ops::Bound;
+use core::future::Future;
+use core::ops::{Bound, RangeBounds};
+use core::pin::Pin;
 
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
-use std::time::SystemTime;
+use std::sync::atomic::{self, AtomicUsize};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use log::{error, warn};
+use tracing::field::Empty;
+use tracing::{debug, error, info_span, warn, Instrument, Span};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use opentelemetry::Context;
 
 use futures::stream::TryStreamExt;
 
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
 
 use tokio_stream::wrappers::ReceiverStream;
 
+use rand::rngs::StdRng;
+use rand::seq::index::sample;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use prost::Message;
+
+use prost_types::value::Kind;
 use prost_types::Value;
 
-use tonic::{Request, Response, Status};
+use tonic::{Request, Response, Status, Streaming};
+
+use crate::pattern::Pattern;
 
+use crate::clients::Clients;
+use crate::memory::{dentry_size, item_size, member_size};
+use crate::memory::{Eviction, Memory, Slot, Usage, EVICTION_SAMPLES_DEFAULT};
+use crate::metrics::Metrics;
+use crate::slowlog::{SlowEntry, SlowLog, SLOWLOG_KEY_MAX};
+use crate::slowlog::{SLOWLOG_MAX_LEN_DEFAULT, SLOWLOG_THRESHOLD_DEFAULT};
+
+use crate::chan::btree::db::{namespace_get, Db, Dbs, Stats};
+
+use crate::acl::{Access, Acl, Rule};
+use crate::auth::Principal;
+use crate::trace::context_get;
+
+use crate::lock::{deadline_new, duration_convert, instant2time, sleep_until, Locks, Waiter};
 use crate::value::btree::Val;
 
 use crate::memdatabase::v1::memory_database_service_server::MemoryDatabaseService;
 
 use crate::memdatabase::v1::bound::Bound as IBound;
+use crate::memdatabase::v1::execute_request::Op;
+use crate::memdatabase::v1::execute_response::Result as ExecResult;
 use crate::memdatabase::v1::Bound as RBound;
+use crate::memdatabase::v1::Val as PVal;
+use crate::memdatabase::v1::ValType;
+use crate::memdatabase::v1::{CopyRequest, CopyResponse};
+use crate::memdatabase::v1::{CountRangeRequest, CountRangeResponse};
+use crate::memdatabase::v1::{DelRangeRequest, DelRangeResponse};
 use crate::memdatabase::v1::{DelRequest, DelResponse};
+use crate::memdatabase::v1::{ExecuteError, ExecuteRequest, ExecuteResponse, RangeResponseList};
+use crate::memdatabase::v1::{ExistsRequest, ExistsResponse};
 use crate::memdatabase::v1::{RangeRequest, RangeResponse};
+use crate::memdatabase::v1::{RenameRequest, RenameResponse};
+use crate::memdatabase::v1::{ScanRequest, ScanResponse};
+use crate::memdatabase::v1::{TypeRequest, TypeResponse};
 
 use crate::memdatabase::v1::{DGetRequest, DGetResponse};
 use crate::memdatabase::v1::{DHasRequest, DHasResponse};
 use crate::memdatabase::v1::{DSetRequest, DSetResponse};
 
 use crate::memdatabase::v1::{GetRequest, GetResponse};
-use crate::memdatabase::v1::{SetRequest, SetResponse};
+use crate::memdatabase::v1::{SetMode, SetRequest, SetResponse};
 
 use crate::memdatabase::v1::{PopRequest, PopResponse};
 use crate::memdatabase::v1::{PushRequest, PushResponse};
 use crate::memdatabase::v1::{QLenRequest, QLenResponse};
 
+use crate::memdatabase::v1::{DIncrByRequest, DIncrByResponse};
+use crate::memdatabase::v1::{IncrByFloatRequest, IncrByFloatResponse};
+use crate::memdatabase::v1::{IncrByRequest, IncrByResponse};
+use crate::memdatabase::v1::{IncrRequest, IncrResponse};
+
+use crate::memdatabase::v1::{AppendRequest, AppendResponse};
+use crate::memdatabase::v1::{GetRangeRequest, GetRangeResponse};
+use crate::memdatabase::v1::{SetRangeRequest, SetRangeResponse};
+use crate::memdatabase::v1::{StrLenRequest, StrLenResponse};
+
+use crate::memdatabase::v1::{AcquireRequest, AcquireResponse};
+use crate::memdatabase::v1::{ReleaseRequest, ReleaseResponse};
+use crate::memdatabase::v1::{RenewRequest, RenewResponse};
+
+use crate::memdatabase::v1::{AclListRequest, AclListResponse, AclRule};
+use crate::memdatabase::v1::{AclWhoAmIRequest, AclWhoAmIResponse};
+use crate::memdatabase::v1::{DbSizeRequest, DbSizeResponse};
+use crate::memdatabase::v1::{FlushDbRequest, FlushDbResponse};
+use crate::memdatabase::v1::{InfoConfig, InfoKeys, InfoRequest, InfoResponse};
+use crate::memdatabase::v1::{MoveRequest, MoveResponse};
+use crate::memdatabase::v1::{Persistence, ReplicationRole};
+use crate::memdatabase::v1::{RandomKeyRequest, RandomKeyResponse};
 use crate::memdatabase::v1::{SAddRequest, SAddResponse};
 use crate::memdatabase::v1::{SDelRequest, SDelResponse};
 use crate::memdatabase::v1::{SLenRequest, SLenResponse};
+use crate::memdatabase::v1::{SPopRequest, SPopResponse};
+use crate::memdatabase::v1::{SRandMemberRequest, SRandMemberResponse};
+
+use crate::memdatabase::v1::{SlowLogEntry, SlowLogGetRequest, SlowLogGetResponse};
+use crate::memdatabase::v1::{SlowLogResetRequest, SlowLogResetResponse};
 
 pub const MAX_RANGE_SIZE_DEFAULT: usize = 10;
 
+/// The number of keys examined by the actor at once in Scan.
+pub const SCAN_BATCH_SIZE_DEFAULT: usize = 1024;
+
+/// The collections larger than this are summarized by their lengths in Range.
+pub const MAX_INLINE_SIZE_DEFAULT: usize = 16;
+
+/// The maximum bytes of a string grown by Append or SetRange.
+pub const MAX_STRING_LENGTH: usize = 536870912;
+
+/// The maximum number of members SRandMember may pick with repeat.
+pub const MAX_RANDOM_MEMBERS: u64 = 65536;
+
+/// The largest integer which can be incremented without losing precision.
+pub const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// The number of operations of an Execute stream which may wait for the results.
+pub const PIPELINE_DEPTH_DEFAULT: usize = 64;
+
+/// The part of the Scan processed by the actor at once.
+pub struct ScanChunk {
+    pub pattern: Arc<Pattern>,
+    pub filter: ValType,
+    pub after: Option<Vec<u8>>,
+}
+
+pub struct ScanPage {
+    pub items: Vec<ScanResponse>,
+
+    /// The last key examined; None if no keys remain.
+    pub next: Option<Vec<u8>>,
+}
+
 pub enum Req {
     Del(DelRequest, Sender<Result<DelResponse, Status>>),
     Range(
         RangeRequest,
         Sender<Receiver<Result<RangeResponse, Status>>>,
     ),
+    Scan(ScanChunk, Sender<Result<ScanPage, Status>>),
 
     Set(SetRequest, Sender<Result<SetResponse, Status>>),
     Get(GetRequest, Sender<Result<GetResponse, Status>>),
@@ -63,6 +169,117 @@ pub enum Req {
     SAdd(SAddRequest, Sender<Result<SAddResponse, Status>>),
     SDel(SDelRequest, Sender<Result<SDelResponse, Status>>),
     SLen(SLenRequest, Sender<Result<SLenResponse, Status>>),
+
+    Incr(IncrRequest, Sender<Result<IncrResponse, Status>>),
+    IncrBy(IncrByRequest, Sender<Result<IncrByResponse, Status>>),
+    IncrByFloat(
+        IncrByFloatRequest,
+        Sender<Result<IncrByFloatResponse, Status>>,
+    ),
+    DIncrBy(DIncrByRequest, Sender<Result<DIncrByResponse, Status>>),
+
+    Append(AppendRequest, Sender<Result<AppendResponse, Status>>),
+    StrLen(StrLenRequest, Sender<Result<StrLenResponse, Status>>),
+    GetRange(GetRangeRequest, Sender<Result<GetRangeResponse, Status>>),
+    SetRange(SetRangeRequest, Sender<Result<SetRangeResponse, Status>>),
+
+    Acquire(AcquireRequest, Sender<Result<AcquireResponse, Status>>),
+    Renew(RenewRequest, Sender<Result<RenewResponse, Status>>),
+    Release(ReleaseRequest, Sender<Result<ReleaseResponse, Status>>),
+
+    DelRange(DelRangeRequest, Sender<Result<DelRangeResponse, Status>>),
+    CountRange(
+        CountRangeRequest,
+        Sender<Result<CountRangeResponse, Status>>,
+    ),
+
+    Exists(ExistsRequest, Sender<Result<ExistsResponse, Status>>),
+    Type(TypeRequest, Sender<Result<TypeResponse, Status>>),
+    Rename(RenameRequest, Sender<Result<RenameResponse, Status>>),
+    Copy(CopyRequest, Sender<Result<CopyResponse, Status>>),
+    SPop(SPopRequest, Sender<Result<SPopResponse, Status>>),
+    SRandMember(
+        SRandMemberRequest,
+        Sender<Result<SRandMemberResponse, Status>>,
+    ),
+    RandomKey(RandomKeyRequest, Sender<Result<RandomKeyResponse, Status>>),
+    FlushDb(FlushDbRequest, Sender<Result<FlushDbResponse, Status>>),
+    DbSize(DbSizeRequest, Sender<Result<DbSizeResponse, Status>>),
+    Move(MoveRequest, Sender<Result<MoveResponse, Status>>),
+
+    SlowLogGet(
+        SlowLogGetRequest,
+        Sender<Result<SlowLogGetResponse, Status>>,
+    ),
+    SlowLogReset(
+        SlowLogResetRequest,
+        Sender<Result<SlowLogResetResponse, Status>>,
+    ),
+
+    /// Gets the statistics of all namespaces for the info.
+    Info(InfoRequest, Sender<Result<Stats, Status>>),
+
+    /// Gets the statistics of all namespaces; not exposed as a command.
+    Stats(Sender<Result<Stats, Status>>),
+}
+
+/// The request to the actor in the namespace.
+pub struct Envelope {
+    pub ns: String,
+    pub req: Req,
+    /// The span of the rpc.
+    pub span: Span,
+    /// Ends when the actor takes the request.
+    pub queued: Span,
+}
+
+/// The namespace and the principal of the request.
+#[derive(Clone)]
+pub struct Caller {
+    pub ns: String,
+    pub principal: Principal,
+    /// The trace context propagated by the client.
+    pub parent: Context,
+}
+
+/// Gets the caller from the metadata and the principal set by the interceptor if any.
+pub fn caller_get<T>(request: &Request<T>) -> Result<Caller, Status> {
+    let ns: String = namespace_get(request.metadata())?;
+    let principal: Principal = request
+        .extensions()
+        .get::<Principal>()
+        .cloned()
+        .unwrap_or_else(Principal::anonymous);
+    let parent: Context = context_get(request.metadata());
+    Ok(Caller {
+        ns,
+        principal,
+        parent,
+    })
+}
+
+/// Creates the span of the rpc; the queueing and the handling are traced as its children.
+pub fn rpc_span(command: &str, caller: &Caller) -> Span {
+    let span: Span = info_span!(
+        "rpc",
+        otel.name = command,
+        otel.kind = "server",
+        otel.status_code = Empty,
+        rpc.system = "grpc",
+        rpc.method = command,
+        namespace = caller.ns.as_str(),
+        principal = caller.principal.name.as_str(),
+    );
+    span.set_parent(caller.parent.clone());
+    span
+}
+
+/// Records the error to the span.
+pub fn span_record<T>(span: &Span, rslt: &Result<T, Status>) {
+    if let Err(e) = rslt {
+        span.record("otel.status_code", "ERROR");
+        debug!(parent: span, code = ?e.code(), "{}", e.message());
+    }
 }
 
 impl Req {
@@ -73,15 +290,32 @@ impl Req {
     ) {
         let key: Vec<u8> = req.key;
         let oval: Option<Value> = req.value;
-        let res: Result<SetResponse, Status> = match oval {
-            None => Err(Status::invalid_argument("no value specified")),
-            Some(val) => {
+        let get: bool = req.get;
+        let res: Result<SetResponse, Status> = (|| {
+            let val: Value = oval.ok_or_else(|| Status::invalid_argument("no value specified"))?;
+            let mode: SetMode = SetMode::try_from(req.mode)
+                .map_err(|_| Status::invalid_argument("invalid mode"))?;
+            let old: Option<&Val> = kv.get(&key);
+            let previous: Option<Value> = match (get, old) {
+                (false, _) => Ok(None),
+                (true, None) => Ok(None),
+                (true, Some(Val::Var(v))) => Ok(Some(v.clone())),
+                (true, Some(_)) => Err(Status::invalid_argument("invalid type")),
+            }?;
+            let written: bool = match mode {
+                SetMode::Unspecified => true,
+                SetMode::Nx => old.is_none(),
+                SetMode::Xx => old.is_some(),
+            };
+            if written {
                 kv.insert(key, Val::Var(val));
-                Ok(SetResponse {
-                    set_time: Some(SystemTime::now().into()),
-                })
             }
-        };
+            Ok(SetResponse {
+                set_time: Some(SystemTime::now().into()),
+                written,
+                previous,
+            })
+        })();
         match reply.send(res).await {
             Ok(_) => {}
             Err(e) => error!("{e}"),
@@ -113,14 +347,16 @@ impl Req {
 }
 
 impl Req {
+    /// Returns the bytes grown.
     pub async fn handle_dset(
         kv: &mut BTreeMap<Vec<u8>, Val>,
         req: DSetRequest,
         reply: Sender<Result<DSetResponse, Status>>,
-    ) {
+    ) -> isize {
         let key: Vec<u8> = req.key;
         let dkey: Vec<u8> = req.dkey;
         let oval: Option<Value> = req.value;
+        let mut grown: isize = 0;
 
         let v: Val = kv.remove(&key).unwrap_or_else(|| Val::Map(BTreeMap::new()));
         let res: Result<DSetResponse, Status> = (|| {
@@ -129,7 +365,10 @@ impl Req {
                 _ => Err(Status::invalid_argument("the key is not a map")),
             }?;
             let val: Value = oval.ok_or_else(|| Status::invalid_argument("the value missing"))?;
+            let added: usize = dentry_size(&dkey, &val);
+            let removed: usize = m.get(&dkey).map(|o| dentry_size(&dkey, o)).unwrap_or(0);
             m.insert(dkey, val);
+            grown = added as isize - removed as isize;
             let cnt: usize = m.len();
             kv.insert(key, Val::Map(m));
             Ok(DSetResponse {
@@ -142,6 +381,7 @@ impl Req {
             Ok(_) => {}
             Err(e) => error!("{e}"),
         }
+        grown
     }
 
     pub async fn handle_dget(
@@ -198,36 +438,45 @@ impl Req {
 }
 
 impl Req {
+    /// Returns the bytes grown.
     pub async fn handle_pop(
         kv: &mut BTreeMap<Vec<u8>, Val>,
         req: PopRequest,
         reply: Sender<Result<PopResponse, Status>>,
-    ) {
+    ) -> isize {
         let key: Vec<u8> = req.key;
         let front: bool = req.front;
+        let count: u64 = req.count.max(1);
+        let mut grown: isize = 0;
         let res: Result<PopResponse, Status> = (|| {
-            let val: Val = kv
-                .remove(&key)
+            let val: &mut Val = kv
+                .get_mut(&key)
                 .ok_or_else(|| Status::not_found("no value found"))?;
-            let mut q: VecDeque<Value> = match val {
+            let q: &mut VecDeque<Value> = match val {
                 Val::Deq(q) => Ok(q),
                 _ => Err(Status::invalid_argument("not a queue")),
             }?;
-            let ov: Option<Value> = match front {
-                true => q.pop_front(),
-                false => q.pop_back(),
+            let n: usize = usize::try_from(count).unwrap_or(usize::MAX).min(q.len());
+            let values: Vec<Value> = match front {
+                true => q.drain(..n).collect(),
+                false => q.drain(q.len() - n..).rev().collect(),
             };
-            let v: Value = ov.ok_or_else(|| Status::not_found("the queue is empty"))?;
-            kv.insert(key, Val::Deq(q));
+            grown = -(values.iter().map(item_size).sum::<usize>() as isize);
+            let v: Value = values
+                .first()
+                .cloned()
+                .ok_or_else(|| Status::not_found("the queue is empty"))?;
             Ok(PopResponse {
                 value: Some(v),
                 pop_time: Some(SystemTime::now().into()),
+                values,
             })
         })();
         match reply.send(res).await {
             Ok(_) => {}
             Err(e) => error!("{e}"),
         }
+        grown
     }
 
     pub async fn handle_qlen(
@@ -253,27 +502,33 @@ impl Req {
         }
     }
 
+    /// Returns the bytes grown.
     pub async fn handle_push(
         kv: &mut BTreeMap<Vec<u8>, Val>,
         req: PushRequest,
         reply: Sender<Result<PushResponse, Status>>,
-    ) {
+    ) -> isize {
         let key: Vec<u8> = req.key;
         let front: bool = req.front;
-        let ov: Option<Value> = req.value;
-        let val: Val = kv.remove(&key).unwrap_or_else(|| Val::Deq(VecDeque::new()));
+        let values: Vec<Value> = req.value.into_iter().chain(req.values).collect();
+        let mut grown: isize = 0;
         let res: Result<PushResponse, Status> = (|| {
-            let mut q: VecDeque<Value> = match val {
+            if values.is_empty() {
+                return Err(Status::invalid_argument("the value missing"));
+            }
+            let val: &mut Val = kv.entry(key).or_insert_with(|| Val::Deq(VecDeque::new()));
+            let q: &mut VecDeque<Value> = match val {
                 Val::Deq(q) => Ok(q),
                 _ => Err(Status::invalid_argument("not a queue")),
             }?;
-            let v: Value = ov.ok_or_else(|| Status::invalid_argument("the value missing"))?;
-            match front {
-                true => q.push_front(v),
-                false => q.push_back(v),
-            };
+            for v in values {
+                grown += item_size(&v) as isize;
+                match front {
+                    true => q.push_front(v),
+                    false => q.push_back(v),
+                };
+            }
             let sz: usize = q.len();
-            kv.insert(key, Val::Deq(q));
             Ok(PushResponse {
                 count: sz as u64,
                 push_time: Some(SystemTime::now().into()),
@@ -283,24 +538,65 @@ impl Req {
             Ok(_) => {}
             Err(e) => error!("{e}"),
         }
+        grown
+    }
+}
+
+/// Picks the members at random; the same member may be picked more than once if repeat.
+///
+/// Walks the set once up to the last member picked; the members are not collected.
+pub fn members_sample(
+    s: &BTreeSet<Vec<u8>>,
+    rng: &mut StdRng,
+    count: u64,
+    repeat: bool,
+) -> Vec<Vec<u8>> {
+    let len: usize = s.len();
+    let count: usize = usize::try_from(count).unwrap_or(usize::MAX);
+    let mut picked: Vec<usize> = match (repeat, len) {
+        (_, 0) => vec![],
+        (true, _) => (0..count).map(|_| rng.gen_range(0..len)).collect(),
+        (false, _) => sample(rng, len, count.min(len)).into_vec(),
+    };
+    picked.sort_unstable();
+    let mut members: Vec<Vec<u8>> = Vec::with_capacity(picked.len());
+    let mut iter = s.iter();
+    // The position of the member the iterator yields next.
+    let mut next: usize = 0;
+    let mut last: Option<&Vec<u8>> = None;
+    for i in picked {
+        if next <= i {
+            last = iter.nth(i - next);
+            next = i + 1;
+        }
+        if let Some(m) = last {
+            members.push(m.clone());
+        }
     }
+    members.shuffle(rng);
+    members
 }
 
 impl Req {
+    /// Returns the bytes grown.
     pub async fn handle_sadd(
         kv: &mut BTreeMap<Vec<u8>, Val>,
         req: SAddRequest,
         reply: Sender<Result<SAddResponse, Status>>,
-    ) {
+    ) -> isize {
         let key: Vec<u8> = req.key;
         let val: Vec<u8> = req.val;
+        let mut grown: isize = 0;
         let v: Val = kv.remove(&key).unwrap_or_else(|| Val::Set(BTreeSet::new()));
         let res: Result<SAddResponse, Status> = (|| {
             let mut s: BTreeSet<Vec<u8>> = match v {
                 Val::Set(s) => Ok(s),
                 _ => Err(Status::invalid_argument("not a set")),
             }?;
-            s.insert(val);
+            let size: usize = member_size(&val);
+            if s.insert(val) {
+                grown = size as isize;
+            }
             let sz: usize = s.len();
             kv.insert(key, Val::Set(s));
             Ok(SAddResponse {
@@ -312,15 +608,18 @@ impl Req {
             Ok(_) => {}
             Err(e) => error!("{e}"),
         }
+        grown
     }
 
+    /// Returns the bytes grown.
     pub async fn handle_sdel(
         kv: &mut BTreeMap<Vec<u8>, Val>,
         req: SDelRequest,
         reply: Sender<Result<SDelResponse, Status>>,
-    ) {
+    ) -> isize {
         let key: Vec<u8> = req.key;
         let val: Vec<u8> = req.val;
+        let mut grown: isize = 0;
         let res: Result<SDelResponse, Status> = (|| {
             let v: &mut Val = kv
                 .get_mut(&key)
@@ -329,7 +628,9 @@ impl Req {
                 Val::Set(s) => Ok(s),
                 _ => Err(Status::invalid_argument("not a set")),
             }?;
-            s.remove(&val);
+            if s.remove(&val) {
+                grown = -(member_size(&val) as isize);
+            }
             let sz: usize = s.len();
             Ok(SDelResponse {
                 count: sz as u64,
@@ -340,6 +641,7 @@ impl Req {
             Ok(_) => {}
             Err(e) => error!("{e}"),
         }
+        grown
     }
 
     pub async fn handle_slen(
@@ -364,189 +666,2186 @@ impl Req {
             Err(e) => error!("{e}"),
         }
     }
-}
 
-pub fn bound_convert(ob: Option<RBound>) -> Result<Bound<Vec<u8>>, Status> {
-    let r: RBound = ob.ok_or_else(|| Status::invalid_argument("invalid bound"))?;
-    let i: IBound = r
-        .bound
-        .ok_or_else(|| Status::invalid_argument("invalid bound"))?;
-    match i {
-        IBound::Included(v) => Ok(Bound::Included(v)),
-        IBound::Excluded(v) => Ok(Bound::Excluded(v)),
+    /// Returns the bytes grown.
+    pub async fn handle_spop(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        rng: &mut StdRng,
+        req: SPopRequest,
+        reply: Sender<Result<SPopResponse, Status>>,
+    ) -> isize {
+        let key: Vec<u8> = req.key;
+        let count: u64 = req.count.max(1);
+        let mut grown: isize = 0;
+        let res: Result<SPopResponse, Status> = (|| {
+            let v: &mut Val = kv
+                .get_mut(&key)
+                .ok_or_else(|| Status::not_found("no set found"))?;
+            let s: &mut BTreeSet<Vec<u8>> = match v {
+                Val::Set(s) => Ok(s),
+                _ => Err(Status::invalid_argument("not a set")),
+            }?;
+            let members: Vec<Vec<u8>> = members_sample(s, rng, count, false);
+            for m in &members {
+                s.remove(m);
+            }
+            grown = -(members.iter().map(|m| member_size(m)).sum::<usize>() as isize);
+            let sz: usize = s.len();
+            Ok(SPopResponse {
+                members,
+                count: sz as u64,
+                spop_time: Some(SystemTime::now().into()),
+            })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+        grown
+    }
+
+    pub async fn handle_srandmember(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        rng: &mut StdRng,
+        req: SRandMemberRequest,
+        reply: Sender<Result<SRandMemberResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let count: u64 = req.count.max(1);
+        let res: Result<SRandMemberResponse, Status> = (|| {
+            // Distinct members are limited by the set itself.
+            match req.repeat && MAX_RANDOM_MEMBERS < count {
+                true => Err(Status::invalid_argument("too many members requested")),
+                false => Ok(()),
+            }?;
+            let v: &Val = kv
+                .get(&key)
+                .ok_or_else(|| Status::not_found("no set found"))?;
+            let s: &BTreeSet<Vec<u8>> = match v {
+                Val::Set(s) => Ok(s),
+                _ => Err(Status::invalid_argument("not a set")),
+            }?;
+            let members: Vec<Vec<u8>> = members_sample(s, rng, count, req.repeat);
+            Ok(SRandMemberResponse { members })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    /// Picks the key from the keys tracked by the memory; the tree has no random access.
+    pub async fn handle_random_key(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        ns: &str,
+        memory: &Memory,
+        rng: &mut StdRng,
+        _req: RandomKeyRequest,
+        reply: Sender<Result<RandomKeyResponse, Status>>,
+    ) {
+        let ok: Option<&[u8]> = memory
+            .random_key(ns, rng)
+            .filter(|key| kv.contains_key(*key));
+        let res: Result<RandomKeyResponse, Status> = Ok(RandomKeyResponse {
+            key: ok.map(|key| key.to_vec()).unwrap_or_default(),
+            found: ok.is_some(),
+        });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
     }
 }
 
-pub fn bound2t<T>(b: &Bound<T>) -> Result<&T, Status> {
-    match b {
-        Bound::Included(t) => Ok(t),
-        Bound::Excluded(t) => Ok(t),
-        Bound::Unbounded => Err(Status::invalid_argument("unexpected bount")),
+/// Adds the delta to the number; the missing number is treated as zero.
+pub fn number_add(ov: Option<&Value>, delta: f64, integer: bool) -> Result<f64, Status> {
+    let current: f64 = match ov.map(|v| v.kind.as_ref()) {
+        None => Ok(0.0),
+        Some(Some(Kind::NumberValue(n))) => Ok(*n),
+        Some(_) => Err(Status::invalid_argument("not a number")),
+    }?;
+    let added: f64 = current + delta;
+    match integer {
+        true => {
+            let safe = |n: f64| n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER;
+            match (safe(current), safe(added)) {
+                (false, _) => Err(Status::invalid_argument("not an integer")),
+                (true, false) => Err(Status::out_of_range("increment would overflow")),
+                (true, true) => Ok(added),
+            }
+        }
+        false => match added.is_finite() {
+            true => Ok(added),
+            false => Err(Status::out_of_range("increment would overflow")),
+        },
     }
 }
 
-pub fn bounds2ord<T>(l: &Bound<T>, u: &Bound<T>) -> Result<Ordering, Status>
-where
-    T: Ord,
-{
-    let lt: &T = bound2t(l)?;
-    let ut: &T = bound2t(u)?;
-    Ok(lt.cmp(ut))
+/// Converts the integer delta; the delta which cannot be kept exactly is rejected.
+pub fn integer_delta(delta: i64) -> Result<f64, Status> {
+    match delta.unsigned_abs() <= MAX_SAFE_INTEGER as u64 {
+        true => Ok(delta as f64),
+        false => Err(Status::out_of_range("delta too large")),
+    }
 }
 
-pub fn check_bound<T>(l: &Bound<T>, u: &Bound<T>) -> Result<(), Status>
-where
-    T: Ord,
-{
-    let o: Ordering = bounds2ord(l, u)?;
-    match o {
-        Ordering::Less => Ok(()),
-        Ordering::Equal => Ok(()),
-        Ordering::Greater => Err(Status::invalid_argument("lower > upper")),
+pub fn number_new(n: f64) -> Value {
+    Value {
+        kind: Some(Kind::NumberValue(n)),
     }
 }
 
 impl Req {
-    pub async fn handle_del(
+    pub fn incr_var(
         kv: &mut BTreeMap<Vec<u8>, Val>,
-        req: DelRequest,
-        reply: Sender<Result<DelResponse, Status>>,
+        key: Vec<u8>,
+        delta: f64,
+        integer: bool,
+    ) -> Result<f64, Status> {
+        let ov: Option<&Value> = match kv.get(&key) {
+            None => Ok(None),
+            Some(Val::Var(v)) => Ok(Some(v)),
+            Some(_) => Err(Status::invalid_argument("invalid type")),
+        }?;
+        let n: f64 = number_add(ov, delta, integer)?;
+        kv.insert(key, Val::Var(number_new(n)));
+        Ok(n)
+    }
+
+    pub async fn handle_incr(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: IncrRequest,
+        reply: Sender<Result<IncrResponse, Status>>,
     ) {
         let key: Vec<u8> = req.key;
-        let res: Result<DelResponse, Status> = {
-            kv.remove(&key);
-            Ok(DelResponse {
-                del_time: Some(SystemTime::now().into()),
-            })
-        };
+        let res: Result<IncrResponse, Status> =
+            Self::incr_var(kv, key, 1.0, true).map(|value: f64| IncrResponse {
+                value,
+                incr_time: Some(SystemTime::now().into()),
+            });
         match reply.send(res).await {
             Ok(_) => {}
             Err(e) => error!("{e}"),
         }
     }
 
-    pub async fn handle_range(
-        kv: &BTreeMap<Vec<u8>, Val>,
-        req: RangeRequest,
-        reply: Sender<Receiver<Result<RangeResponse, Status>>>,
-        conf: &Conf,
+    pub async fn handle_incr_by(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: IncrByRequest,
+        reply: Sender<Result<IncrByResponse, Status>>,
     ) {
-        let max: usize = conf.max_range;
-        let ol: Option<RBound> = req.lower;
-        let ou: Option<RBound> = req.upper;
-        let keys: Result<Vec<Vec<u8>>, Status> = (|| {
-            let l: Bound<Vec<u8>> = bound_convert(ol)?;
-            let u: Bound<Vec<u8>> = bound_convert(ou)?;
-            check_bound(&l, &u)?;
-            let pairs = kv.range((l, u));
-            let taken = pairs.take(max);
-            let keys = taken.map(|pair| pair.0).cloned();
-            Ok(keys.collect())
-        })();
-        let (tx, rx) = tokio::sync::mpsc::channel(1);
-        tokio::spawn(async move {
-            let tx = &tx;
-            match keys {
-                Err(e) => match tx.send(Err(e.clone())).await {
-                    Ok(_) => warn!("{e}"),
-                    Err(e) => error!("{e}"),
-                },
-                Ok(v) => {
-                    let mapd = v.into_iter().map(|key: Vec<u8>| Ok(RangeResponse { key }));
-                    let strm = futures::stream::iter(mapd);
-                    let rcnt: Result<u64, Status> = strm
-                        .try_fold(0, |state, next| async move {
-                            tx.send(Ok(next))
-                                .await
-                                .map(|_| state + 1)
-                                .map_err(|e| Status::internal(format!("unable to send: {e}")))
-                        })
-                        .await;
-                    match rcnt {
-                        Ok(_) => {}
-                        Err(e) => error!("{e}"),
-                    }
-                }
-            }
-        });
-        match reply.send(rx).await {
+        let key: Vec<u8> = req.key;
+        let res: Result<IncrByResponse, Status> = integer_delta(req.delta)
+            .and_then(|delta: f64| Self::incr_var(kv, key, delta, true))
+            .map(|value: f64| IncrByResponse {
+                value,
+                incr_time: Some(SystemTime::now().into()),
+            });
+        match reply.send(res).await {
             Ok(_) => {}
             Err(e) => error!("{e}"),
         }
     }
-}
 
-pub struct Conf {
-    pub max_range: usize,
-}
+    pub async fn handle_incr_by_float(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: IncrByFloatRequest,
+        reply: Sender<Result<IncrByFloatResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let delta: f64 = req.delta;
+        let res: Result<IncrByFloatResponse, Status> =
+            Self::incr_var(kv, key, delta, false).map(|value: f64| IncrByFloatResponse {
+                value,
+                incr_time: Some(SystemTime::now().into()),
+            });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
 
-impl Default for Conf {
-    fn default() -> Self {
-        Self {
-            max_range: MAX_RANGE_SIZE_DEFAULT,
+    /// Returns the bytes grown.
+    pub async fn handle_dincr_by(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: DIncrByRequest,
+        reply: Sender<Result<DIncrByResponse, Status>>,
+    ) -> isize {
+        let key: Vec<u8> = req.key;
+        let dkey: Vec<u8> = req.dkey;
+        let mut grown: isize = 0;
+        let res: Result<DIncrByResponse, Status> = (|| {
+            let delta: f64 = integer_delta(req.delta)?;
+            // Validates before creating the map not to leave an empty map on failure.
+            let current: Option<&Value> = match kv.get(&key) {
+                None => None,
+                Some(Val::Map(m)) => m.get(&dkey),
+                Some(_) => return Err(Status::invalid_argument("not a map")),
+            };
+            let n: f64 = number_add(current, delta, true)?;
+            let removed: usize = current.map(|o| dentry_size(&dkey, o)).unwrap_or(0);
+            let val: Value = number_new(n);
+            grown = dentry_size(&dkey, &val) as isize - removed as isize;
+            if let Val::Map(m) = kv.entry(key).or_insert_with(|| Val::Map(BTreeMap::new())) {
+                m.insert(dkey, val);
+            }
+            Ok(DIncrByResponse {
+                value: n,
+                incr_time: Some(SystemTime::now().into()),
+            })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
         }
+        grown
     }
 }
 
-impl Req {
-    pub async fn handle(self, kv: &mut BTreeMap<Vec<u8>, Val>, conf: &Conf) {
-        match self {
-            Self::Set(req, reply) => Self::handle_set(kv, req, reply).await,
-            Self::Get(req, reply) => Self::handle_get(kv, req, reply).await,
-            Self::DSet(req, reply) => Self::handle_dset(kv, req, reply).await,
-            Self::DGet(req, reply) => Self::handle_dget(kv, req, reply).await,
-            Self::DHas(req, reply) => Self::handle_dhas(kv, req, reply).await,
-            Self::Push(req, reply) => Self::handle_push(kv, req, reply).await,
-            Self::Pop(req, reply) => Self::handle_pop(kv, req, reply).await,
-            Self::QLen(req, reply) => Self::handle_qlen(kv, req, reply).await,
-            Self::SAdd(req, reply) => Self::handle_sadd(kv, req, reply).await,
-            Self::SDel(req, reply) => Self::handle_sdel(kv, req, reply).await,
-            Self::SLen(req, reply) => Self::handle_slen(kv, req, reply).await,
-            Self::Del(req, reply) => Self::handle_del(kv, req, reply).await,
-            Self::Range(req, reply) => Self::handle_range(kv, req, reply, conf).await,
-        }
+/// Gets the string specified by the key; the missing key is treated as an empty string.
+pub fn string_ref<'a>(kv: &'a BTreeMap<Vec<u8>, Val>, key: &[u8]) -> Result<&'a str, Status> {
+    match kv.get(key) {
+        None => Ok(""),
+        Some(Val::Var(Value {
+            kind: Some(Kind::StringValue(s)),
+        })) => Ok(s.as_str()),
+        Some(Val::Var(_)) => Err(Status::invalid_argument("not a string")),
+        Some(_) => Err(Status::invalid_argument("invalid type")),
     }
 }
 
-pub struct ChanSvc {
-    sender: Sender<Req>,
+/// Gets the string specified by the key; the missing key is created as an empty string.
+pub fn string_mut(kv: &mut BTreeMap<Vec<u8>, Val>, key: Vec<u8>) -> Result<&mut String, Status> {
+    let v: &mut Val = kv.entry(key).or_insert_with(|| {
+        Val::Var(Value {
+            kind: Some(Kind::StringValue(String::new())),
+        })
+    });
+    match v {
+        Val::Var(Value {
+            kind: Some(Kind::StringValue(s)),
+        }) => Ok(s),
+        Val::Var(_) => Err(Status::invalid_argument("not a string")),
+        _ => Err(Status::invalid_argument("invalid type")),
+    }
 }
 
-#[tonic::async_trait]
-impl MemoryDatabaseService for ChanSvc {
-    type RangeStream = ReceiverStream<Result<RangeResponse, Status>>;
+/// Gets the bytes between start and end(inclusive) like GETRANGE of Redis.
+/// A character cut at either end is replaced with U+FFFD.
+pub fn substring(s: &str, start: i64, end: i64) -> String {
+    let len: i64 = s.len() as i64;
+    let norm = |i: i64| match i < 0 {
+        true => (len + i).max(0),
+        false => i,
+    };
+    let b: i64 = norm(start);
+    let e: i64 = norm(end).min(len - 1);
+    match b <= e {
+        true => String::from_utf8_lossy(&s.as_bytes()[b as usize..=e as usize]).into_owned(),
+        false => String::new(),
+    }
+}
 
-    async fn set(
-        &self,
-        request: Request<SetRequest>,
-    ) -> std::result::Result<Response<SetResponse>, Status> {
-        let iq: SetRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let req = Req::Set(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: SetResponse = rslt?;
-        Ok(Response::new(res))
+/// Checks the bytes from the offset can be overwritten without cutting a character.
+pub fn check_overwrite(s: &str, offset: usize, value: &str) -> Result<(), Status> {
+    let end: usize = offset.saturating_add(value.len()).min(s.len());
+    let cut: bool = offset < s.len() && !(s.is_char_boundary(offset) && s.is_char_boundary(end));
+    match cut {
+        true => Err(Status::invalid_argument("not on a character boundary")),
+        false => Ok(()),
     }
+}
 
-    async fn get(
-        &self,
-        request: Request<GetRequest>,
-    ) -> std::result::Result<Response<GetResponse>, Status> {
-        let iq: GetRequest = request.into_inner();
+/// Overwrites the bytes from the offset, padding the string with NUL if required.
+pub fn overwrite(s: &mut String, offset: usize, value: &str) {
+    s.extend(core::iter::repeat_n('\0', offset.saturating_sub(s.len())));
+    let end: usize = (offset + value.len()).min(s.len());
+    s.replace_range(offset..end, value);
+}
+
+impl Req {
+    pub async fn handle_append(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: AppendRequest,
+        reply: Sender<Result<AppendResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let value: String = req.value;
+        let res: Result<AppendResponse, Status> = (|| {
+            let too_long = || Status::out_of_range("string exceeds maximum allowed size");
+            // Checked first not to create the empty string on failure.
+            match value.len() <= MAX_STRING_LENGTH {
+                true => Ok(()),
+                false => Err(too_long()),
+            }?;
+            let s: &mut String = string_mut(kv, key)?;
+            let len: usize = s.len() + value.len();
+            match len <= MAX_STRING_LENGTH {
+                true => Ok(()),
+                false => Err(too_long()),
+            }?;
+            s.push_str(&value);
+            Ok(AppendResponse {
+                length: len as u64,
+                append_time: Some(SystemTime::now().into()),
+            })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_strlen(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        req: StrLenRequest,
+        reply: Sender<Result<StrLenResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let res: Result<StrLenResponse, Status> = string_ref(kv, &key).map(|s: &str| {
+            let sz: usize = s.len();
+            StrLenResponse { length: sz as u64 }
+        });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_getrange(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        req: GetRangeRequest,
+        reply: Sender<Result<GetRangeResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let res: Result<GetRangeResponse, Status> = string_ref(kv, &key).map(|s: &str| {
+            let sz: usize = s.len();
+            GetRangeResponse {
+                value: substring(s, req.start, req.end),
+                length: sz as u64,
+            }
+        });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_setrange(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: SetRangeRequest,
+        reply: Sender<Result<SetRangeResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let value: String = req.value;
+        let res: Result<SetRangeResponse, Status> = (|| {
+            let offset: usize = match req.offset < 0 {
+                true => Err(Status::invalid_argument("negative offset")),
+                false => Ok(req.offset as u64),
+            }
+            .map(|o: u64| usize::try_from(o).unwrap_or(usize::MAX))?;
+            match offset.saturating_add(value.len()) <= MAX_STRING_LENGTH {
+                true => Ok(()),
+                false => Err(Status::out_of_range("string exceeds maximum allowed size")),
+            }?;
+            let current: &str = string_ref(kv, &key)?;
+            check_overwrite(current, offset, &value)?;
+            let len: usize = match value.is_empty() {
+                true => current.len(),
+                false => {
+                    let s: &mut String = string_mut(kv, key)?;
+                    overwrite(s, offset, &value);
+                    s.len()
+                }
+            };
+            Ok(SetRangeResponse {
+                length: len as u64,
+                setrange_time: Some(SystemTime::now().into()),
+            })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+}
+
+impl Req {
+    pub async fn handle_acquire(
+        locks: &mut Locks,
+        req: AcquireRequest,
+        reply: Sender<Result<AcquireResponse, Status>>,
+    ) {
+        let now: Instant = Instant::now();
+        let name: Vec<u8> = req.name;
+        let owner: String = req.owner;
+        let chk: Result<(Duration, Instant), Status> = (|| {
+            let ttl: Duration = duration_convert(req.ttl)?;
+            let wait: Duration = req
+                .wait
+                .map(|w| duration_convert(Some(w)))
+                .transpose()?
+                .unwrap_or_default();
+            match ttl.is_zero() {
+                true => Err(Status::invalid_argument("zero ttl")),
+                false => Ok(()),
+            }?;
+            let deadline: Instant = deadline_new(now, wait)?;
+            deadline_new(deadline, ttl)?;
+            Ok((ttl, deadline))
+        })();
+        match chk {
+            Ok((ttl, deadline)) => {
+                let waiter = Waiter {
+                    owner,
+                    ttl,
+                    deadline,
+                    reply,
+                };
+                locks.acquire(name, waiter, now);
+            }
+            Err(e) => match reply.send(Err(e)).await {
+                Ok(_) => {}
+                Err(e) => error!("{e}"),
+            },
+        }
+    }
+
+    pub async fn handle_renew(
+        locks: &mut Locks,
+        req: RenewRequest,
+        reply: Sender<Result<RenewResponse, Status>>,
+    ) {
+        let now: Instant = Instant::now();
+        let res: Result<RenewResponse, Status> = (|| {
+            let ttl: Duration = duration_convert(req.ttl)?;
+            let expires: Instant = deadline_new(now, ttl)?;
+            let renewed: Instant = locks.renew(&req.name, &req.owner, req.token, expires, now)?;
+            Ok(RenewResponse {
+                expire_time: Some(instant2time(renewed, now).into()),
+            })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_release(
+        locks: &mut Locks,
+        req: ReleaseRequest,
+        reply: Sender<Result<ReleaseResponse, Status>>,
+    ) {
+        let now: Instant = Instant::now();
+        let released: bool = locks.release(&req.name, &req.owner, req.token, now);
+        match reply.send(Ok(ReleaseResponse { released })).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+}
+
+pub fn bound_convert(ob: Option<RBound>) -> Result<Bound<Vec<u8>>, Status> {
+    let r: RBound = ob.ok_or_else(|| Status::invalid_argument("invalid bound"))?;
+    let i: IBound = r
+        .bound
+        .ok_or_else(|| Status::invalid_argument("invalid bound"))?;
+    match i {
+        IBound::Included(v) => Ok(Bound::Included(v)),
+        IBound::Excluded(v) => Ok(Bound::Excluded(v)),
+        IBound::Unbounded(_) => Ok(Bound::Unbounded),
+    }
+}
+
+pub fn bound2t<T>(b: &Bound<T>) -> Result<&T, Status> {
+    match b {
+        Bound::Included(t) => Ok(t),
+        Bound::Excluded(t) => Ok(t),
+        Bound::Unbounded => Err(Status::invalid_argument("unexpected bount")),
+    }
+}
+
+pub fn bounds2ord<T>(l: &Bound<T>, u: &Bound<T>) -> Result<Ordering, Status>
+where
+    T: Ord,
+{
+    let lt: &T = bound2t(l)?;
+    let ut: &T = bound2t(u)?;
+    Ok(lt.cmp(ut))
+}
+
+pub fn check_bound<T>(l: &Bound<T>, u: &Bound<T>) -> Result<(), Status>
+where
+    T: Ord,
+{
+    if let (Bound::Unbounded, _) | (_, Bound::Unbounded) = (l, u) {
+        return Ok(());
+    }
+    let o: Ordering = bounds2ord(l, u)?;
+    match o {
+        Ordering::Less => Ok(()),
+        Ordering::Equal => Ok(()),
+        Ordering::Greater => Err(Status::invalid_argument("lower > upper")),
+    }
+}
+
+/// Checks if the range may contain keys; BTreeMap::range panics for some empty ranges.
+pub fn range_nonempty<T>(l: &Bound<T>, u: &Bound<T>) -> bool
+where
+    T: Ord,
+{
+    match (l, u) {
+        (Bound::Unbounded, _) => true,
+        (_, Bound::Unbounded) => true,
+        (Bound::Included(a), Bound::Included(b)) => a <= b,
+        (Bound::Included(a), Bound::Excluded(b)) => a < b,
+        (Bound::Excluded(a), Bound::Included(b)) => a < b,
+        (Bound::Excluded(a), Bound::Excluded(b)) => a < b,
+    }
+}
+
+/// The keys found and the last key examined if more keys may remain.
+pub type Examined<'a> = (Vec<(&'a Vec<u8>, &'a Val)>, Option<&'a Vec<u8>>);
+
+/// Examines at most the budget keys; returns the keys of the type(up to the limit + 1)
+/// and the last key examined if the budget ran out.
+pub fn range_examine<'a, I>(pairs: I, budget: usize, filter: ValType, limit: usize) -> Examined<'a>
+where
+    I: Iterator<Item = (&'a Vec<u8>, &'a Val)>,
+{
+    let mut examined: usize = 0;
+    let mut last: Option<&Vec<u8>> = None;
+    let found: Vec<(&Vec<u8>, &Val)> = pairs
+        .take(budget)
+        .inspect(|pair| {
+            examined += 1;
+            last = Some(pair.0);
+        })
+        .filter(|pair| pair.1.is_type(filter))
+        .take(limit + 1)
+        .collect();
+    match examined < budget {
+        true => (found, None),
+        false => (found, last),
+    }
+}
+
+pub const CURSOR_TAG: u8 = 1;
+
+/// Creates the opaque cursor which resumes the scan after the key.
+pub fn cursor_new(key: &[u8]) -> Vec<u8> {
+    let mut cursor: Vec<u8> = Vec::with_capacity(key.len() + 1);
+    cursor.push(CURSOR_TAG);
+    cursor.extend_from_slice(key);
+    cursor
+}
+
+pub fn cursor2key(cursor: &[u8]) -> Result<Option<&[u8]>, Status> {
+    match cursor.split_first() {
+        None => Ok(None),
+        Some((&CURSOR_TAG, key)) => Ok(Some(key)),
+        Some(_) => Err(Status::invalid_argument("invalid cursor")),
+    }
+}
+
+/// The lower and the upper bounds of the keys.
+pub type KeyRange = (Bound<Vec<u8>>, Bound<Vec<u8>>);
+
+/// Narrows the range to resume after the cursor; rejects the cursor out of the range.
+pub fn cursor_apply(
+    l: Bound<Vec<u8>>,
+    u: Bound<Vec<u8>>,
+    cursor: &[u8],
+    reverse: bool,
+) -> Result<KeyRange, Status> {
+    let key: Vec<u8> = match cursor2key(cursor)? {
+        None => return Ok((l, u)),
+        Some(k) => k.to_vec(),
+    };
+    let within: bool = RangeBounds::<Vec<u8>>::contains(&(l.as_ref(), u.as_ref()), &key);
+    match within {
+        true => Ok(()),
+        false => Err(Status::invalid_argument("cursor out of the range")),
+    }?;
+    match reverse {
+        false => Ok((Bound::Excluded(key), u)),
+        true => Ok((l, Bound::Excluded(key))),
+    }
+}
+
+impl Req {
+    pub async fn handle_del(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: DelRequest,
+        reply: Sender<Result<DelResponse, Status>>,
+    ) {
+        let keys: Vec<Vec<u8>> = match req.keys.is_empty() {
+            true => vec![req.key],
+            false => req.keys,
+        };
+        let removed: Vec<Option<Val>> = keys.iter().map(|key| kv.remove(key)).collect();
+        let existed: Vec<bool> = removed.iter().map(Option::is_some).collect();
+        let cnt: usize = existed.iter().filter(|e| **e).count();
+        let values: Vec<PVal> = match req.return_values {
+            true => removed
+                .iter()
+                .map(|ov| ov.as_ref().map(PVal::from).unwrap_or_default())
+                .collect(),
+            false => vec![],
+        };
+        let res: Result<DelResponse, Status> = Ok(DelResponse {
+            del_time: Some(SystemTime::now().into()),
+            count: cnt as u64,
+            existed,
+            values,
+        });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_exists(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        req: ExistsRequest,
+        reply: Sender<Result<ExistsResponse, Status>>,
+    ) {
+        let found: Vec<bool> = req.keys.iter().map(|key| kv.contains_key(key)).collect();
+        let cnt: usize = found.iter().filter(|f| **f).count();
+        let res: Result<ExistsResponse, Status> = Ok(ExistsResponse {
+            count: cnt as u64,
+            found,
+        });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_type(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        req: TypeRequest,
+        reply: Sender<Result<TypeResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let t: ValType = kv.get(&key).map(Val::val_type).unwrap_or_default();
+        let res: Result<TypeResponse, Status> = Ok(TypeResponse { val_type: t.into() });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_rename(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: RenameRequest,
+        reply: Sender<Result<RenameResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let new_key: Vec<u8> = req.new_key;
+        let res: Result<RenameResponse, Status> = (|| {
+            match kv.contains_key(&key) {
+                true => Ok(()),
+                false => Err(Status::not_found("no value found")),
+            }?;
+            let renamed: bool = key == new_key || !(req.nx && kv.contains_key(&new_key));
+            if renamed && key != new_key {
+                if let Some(v) = kv.remove(&key) {
+                    kv.insert(new_key, v);
+                }
+            }
+            Ok(RenameResponse {
+                renamed,
+                rename_time: Some(SystemTime::now().into()),
+            })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_copy(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: CopyRequest,
+        reply: Sender<Result<CopyResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let new_key: Vec<u8> = req.new_key;
+        let res: Result<CopyResponse, Status> = (|| {
+            let v: &Val = kv
+                .get(&key)
+                .ok_or_else(|| Status::not_found("no value found"))?;
+            let copied: bool = req.replace || !kv.contains_key(&new_key);
+            if copied {
+                let cloned: Val = v.clone();
+                kv.insert(new_key, cloned);
+            }
+            Ok(CopyResponse {
+                copied,
+                copy_time: Some(SystemTime::now().into()),
+            })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_del_range(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        req: DelRangeRequest,
+        reply: Sender<Result<DelRangeResponse, Status>>,
+    ) {
+        let res: Result<DelRangeResponse, Status> = (|| {
+            let l: Bound<Vec<u8>> = bound_convert(req.lower)?;
+            let u: Bound<Vec<u8>> = bound_convert(req.upper)?;
+            check_bound(&l, &u)?;
+            let keys: Vec<Vec<u8>> = match range_nonempty(&l, &u) {
+                true => kv.range((l, u)).map(|pair| pair.0.clone()).collect(),
+                false => vec![],
+            };
+            for key in &keys {
+                kv.remove(key);
+            }
+            Ok(DelRangeResponse {
+                count: keys.len() as u64,
+                del_time: Some(SystemTime::now().into()),
+            })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_count_range(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        req: CountRangeRequest,
+        reply: Sender<Result<CountRangeResponse, Status>>,
+    ) {
+        let res: Result<CountRangeResponse, Status> = (|| {
+            let l: Bound<Vec<u8>> = bound_convert(req.lower)?;
+            let u: Bound<Vec<u8>> = bound_convert(req.upper)?;
+            check_bound(&l, &u)?;
+            let cnt: usize = match range_nonempty(&l, &u) {
+                true => kv.range((l, u)).count(),
+                false => 0,
+            };
+            Ok(CountRangeResponse { count: cnt as u64 })
+        })();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_range(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        req: RangeRequest,
+        reply: Sender<Receiver<Result<RangeResponse, Status>>>,
+        conf: &Conf,
+    ) {
+        let max: usize = conf.max_range.max(1);
+        let limit: usize = match req.limit {
+            0 => max,
+            l => usize::try_from(l).unwrap_or(max).min(max),
+        };
+        let reverse: bool = req.reverse;
+        let with_values: bool = req.with_values;
+        let ol: Option<RBound> = req.lower;
+        let ou: Option<RBound> = req.upper;
+        let items: Result<Vec<RangeResponse>, Status> = (|| {
+            let filter: ValType = ValType::try_from(req.type_filter)
+                .map_err(|_| Status::invalid_argument("invalid type filter"))?;
+            let l: Bound<Vec<u8>> = bound_convert(ol)?;
+            let u: Bound<Vec<u8>> = bound_convert(ou)?;
+            check_bound(&l, &u)?;
+            let (l, u) = cursor_apply(l, u, &req.cursor, reverse)?;
+            if !range_nonempty(&l, &u) {
+                return Ok(vec![]);
+            }
+            // The type filter may skip many keys; the keys examined are bounded as well.
+            let budget: usize = conf.scan_batch.max(limit + 1);
+            let pairs = kv.range((l, u));
+            let (mut found, rest) = match reverse {
+                true => range_examine(pairs.rev(), budget, filter, limit),
+                false => range_examine(pairs, budget, filter, limit),
+            };
+            let more: bool = limit < found.len();
+            found.truncate(limit);
+            let next: Option<Vec<u8>> = match (more, rest) {
+                (true, _) => found.last().map(|pair| cursor_new(pair.0)),
+                (false, Some(key)) => Some(cursor_new(key)),
+                (false, None) => None,
+            };
+            let cnt: usize = found.len();
+            let cursor_only: Option<RangeResponse> = match cnt {
+                0 => next.clone().map(|next_cursor| RangeResponse {
+                    next_cursor,
+                    cursor_only: true,
+                    ..Default::default()
+                }),
+                _ => None,
+            };
+            let mapd = found.into_iter().enumerate().map(|(i, (key, val))| {
+                let next_cursor: Vec<u8> = match i + 1 == cnt {
+                    true => next.clone().unwrap_or_default(),
+                    false => vec![],
+                };
+                let (val_type, value, length) = match with_values {
+                    true => (
+                        val.val_type(),
+                        Some(val)
+                            .filter(|v| v.len() <= conf.max_inline)
+                            .map(PVal::from),
+                        val.len() as u64,
+                    ),
+                    false => (ValType::Unspecified, None, 0),
+                };
+                RangeResponse {
+                    key: key.clone(),
+                    next_cursor,
+                    val_type: val_type.into(),
+                    value,
+                    length,
+                    cursor_only: false,
+                }
+            });
+            Ok(mapd.chain(cursor_only).collect())
+        })();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let tx = &tx;
+            match items {
+                Err(e) => match tx.send(Err(e.clone())).await {
+                    Ok(_) => warn!("{e}"),
+                    Err(e) => error!("{e}"),
+                },
+                Ok(v) => {
+                    let mapd = v.into_iter().map(Ok);
+                    let strm = futures::stream::iter(mapd);
+                    let rcnt: Result<u64, Status> = strm
+                        .try_fold(0, |state, next| async move {
+                            tx.send(Ok(next))
+                                .await
+                                .map(|_| state + 1)
+                                .map_err(|e| Status::internal(format!("unable to send: {e}")))
+                        })
+                        .await;
+                    match rcnt {
+                        Ok(_) => {}
+                        Err(e) => error!("{e}"),
+                    }
+                }
+            }
+        });
+        match reply.send(rx).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+}
+
+impl Req {
+    pub async fn handle_scan(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        chunk: ScanChunk,
+        reply: Sender<Result<ScanPage, Status>>,
+        conf: &Conf,
+    ) {
+        let batch: usize = conf.scan_batch.max(1);
+        let pattern: &Pattern = &chunk.pattern;
+        let (l, u) = pattern.range(chunk.after);
+        let page: ScanPage = match range_nonempty(&l, &u) {
+            false => ScanPage {
+                items: vec![],
+                next: None,
+            },
+            true => {
+                let examined: Vec<(&Vec<u8>, &Val)> = kv.range((l, u)).take(batch).collect();
+                let next: Option<Vec<u8>> = match examined.len() < batch {
+                    true => None,
+                    false => examined.last().map(|pair| pair.0.clone()),
+                };
+                let items: Vec<ScanResponse> = examined
+                    .into_iter()
+                    .filter(|(key, val)| val.is_type(chunk.filter) && pattern.is_match(key))
+                    .map(|(key, val)| ScanResponse {
+                        key: key.clone(),
+                        val_type: val.val_type().into(),
+                    })
+                    .collect();
+                ScanPage { items, next }
+            }
+        };
+        match reply.send(Ok(page)).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+}
+
+/// Sends the chunks to the actor one by one so that other requests can be handled between them.
+///
+/// Fails with UNAVAILABLE once closing; the rest of the keys are not scanned.
+pub async fn scan_send(
+    svc: ChanSvc,
+    ns: String,
+    pattern: Pattern,
+    filter: ValType,
+    reply: Sender<Result<ScanResponse, Status>>,
+) {
+    let pattern: Arc<Pattern> = Arc::new(pattern);
+    let mut closing: watch::Receiver<bool> = svc.closing.clone();
+    let mut after: Option<Vec<u8>> = None;
+    let unavailable = || Status::unavailable("shutting down");
+    loop {
+        if *closing.borrow() {
+            return reply_err(reply, unavailable()).await;
+        }
+        let chunk = ScanChunk {
+            pattern: pattern.clone(),
+            filter,
+            after: after.take(),
+        };
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let req = Req::Get(iq, tx);
-        self.sender
-            .send(req)
+        let req = Req::Scan(chunk, tx);
+        let env = Envelope::new(ns.clone(), req);
+        let started: Instant = Instant::now();
+        let rpage: Result<ScanPage, Status> = match svc.sender.send(env).await {
+            Ok(_) => rx
+                .recv()
+                .await
+                .unwrap_or_else(|| Err(Status::internal("no response got"))),
+            Err(e) => Err(e),
+        };
+        svc.time("Scan", started);
+        let page: ScanPage = match rpage {
+            Ok(page) => page,
+            Err(e) => {
+                match reply.send(Err(e)).await {
+                    Ok(_) => {}
+                    Err(e) => error!("{e}"),
+                }
+                return;
+            }
+        };
+        for item in page.items {
+            // The client may stop reading; the stream ends anyway once closing.
+            let sent: Result<(), _> = tokio::select! {
+                sent = reply.send(Ok(item)) => sent,
+                _ = closed(&mut closing) => return reply_err(reply, unavailable()).await,
+            };
+            match sent {
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("the client gone: {e}");
+                    return;
+                }
+            }
+        }
+        match page.next {
+            None => return,
+            Some(next) => after = Some(next),
+        }
+    }
+}
+
+pub struct Conf {
+    pub max_range: usize,
+    pub max_inline: usize,
+    pub scan_batch: usize,
+
+    /// The seed of the random number generator; seeded by the OS if none.
+    pub seed: Option<u64>,
+
+    /// The approximate bytes the keys may use; unlimited if zero.
+    pub max_memory: usize,
+    pub eviction: Eviction,
+
+    /// The number of the keys sampled to pick the key to evict.
+    pub eviction_samples: usize,
+
+    pub acl: Arc<Acl>,
+
+    /// Observes the requests if any.
+    pub metrics: Option<Arc<Metrics>>,
+
+    /// Logs the requests handled slower than this.
+    pub slowlog_threshold: Duration,
+
+    /// The number of the slow requests kept; off if zero.
+    pub slowlog_max_len: usize,
+
+    /// Counts the connections of the servers.
+    pub clients: Arc<Clients>,
+}
+
+impl Default for Conf {
+    fn default() -> Self {
+        Self {
+            max_range: MAX_RANGE_SIZE_DEFAULT,
+            max_inline: MAX_INLINE_SIZE_DEFAULT,
+            scan_batch: SCAN_BATCH_SIZE_DEFAULT,
+            seed: None,
+            max_memory: 0,
+            eviction: Eviction::NoEviction,
+            eviction_samples: EVICTION_SAMPLES_DEFAULT,
+            acl: Arc::new(Acl::default()),
+            metrics: None,
+            slowlog_threshold: SLOWLOG_THRESHOLD_DEFAULT,
+            slowlog_max_len: SLOWLOG_MAX_LEN_DEFAULT,
+            clients: Arc::new(Clients::default()),
+        }
+    }
+}
+
+impl Conf {
+    /// Gets the values reported by the info.
+    pub fn info(&self) -> InfoConfig {
+        InfoConfig {
+            max_range: self.max_range as u64,
+            max_inline: self.max_inline as u64,
+            scan_batch: self.scan_batch as u64,
+            max_memory: self.max_memory as u64,
+            eviction: self.eviction.as_str().into(),
+            eviction_samples: self.eviction_samples as u64,
+            seeded: self.seed.is_some(),
+            acl_enabled: self.acl.is_enabled(),
+            metrics_enabled: self.metrics.is_some(),
+            slowlog_threshold: prost_types::Duration::try_from(self.slowlog_threshold).ok(),
+            slowlog_max_len: self.slowlog_max_len as u64,
+        }
+    }
+}
+
+impl Req {
+    /// Returns the bytes grown if known; the collections are too large to measure again.
+    pub async fn handle(
+        self,
+        dbs: &mut Dbs,
+        ns: &str,
+        memory: &Memory,
+        rng: &mut StdRng,
+        conf: &Conf,
+    ) -> Option<isize> {
+        let db: &mut Db = dbs.get_mut(ns);
+        let kv: &mut BTreeMap<Vec<u8>, Val> = &mut db.kv;
+        let locks: &mut Locks = &mut db.locks;
+        let mut grown: Option<isize> = None;
+        match self {
+            Self::Set(req, reply) => Self::handle_set(kv, req, reply).await,
+            Self::Get(req, reply) => Self::handle_get(kv, req, reply).await,
+            Self::DSet(req, reply) => grown = Some(Self::handle_dset(kv, req, reply).await),
+            Self::DGet(req, reply) => Self::handle_dget(kv, req, reply).await,
+            Self::DHas(req, reply) => Self::handle_dhas(kv, req, reply).await,
+            Self::Push(req, reply) => grown = Some(Self::handle_push(kv, req, reply).await),
+            Self::Pop(req, reply) => grown = Some(Self::handle_pop(kv, req, reply).await),
+            Self::QLen(req, reply) => Self::handle_qlen(kv, req, reply).await,
+            Self::SAdd(req, reply) => grown = Some(Self::handle_sadd(kv, req, reply).await),
+            Self::SDel(req, reply) => grown = Some(Self::handle_sdel(kv, req, reply).await),
+            Self::SLen(req, reply) => Self::handle_slen(kv, req, reply).await,
+            Self::Del(req, reply) => Self::handle_del(kv, req, reply).await,
+            Self::Range(req, reply) => Self::handle_range(kv, req, reply, conf).await,
+            Self::Scan(chunk, reply) => Self::handle_scan(kv, chunk, reply, conf).await,
+            Self::Incr(req, reply) => Self::handle_incr(kv, req, reply).await,
+            Self::IncrBy(req, reply) => Self::handle_incr_by(kv, req, reply).await,
+            Self::IncrByFloat(req, reply) => Self::handle_incr_by_float(kv, req, reply).await,
+            Self::DIncrBy(req, reply) => grown = Some(Self::handle_dincr_by(kv, req, reply).await),
+            Self::Append(req, reply) => Self::handle_append(kv, req, reply).await,
+            Self::StrLen(req, reply) => Self::handle_strlen(kv, req, reply).await,
+            Self::GetRange(req, reply) => Self::handle_getrange(kv, req, reply).await,
+            Self::SetRange(req, reply) => Self::handle_setrange(kv, req, reply).await,
+            Self::Acquire(req, reply) => Self::handle_acquire(locks, req, reply).await,
+            Self::Renew(req, reply) => Self::handle_renew(locks, req, reply).await,
+            Self::Release(req, reply) => Self::handle_release(locks, req, reply).await,
+            Self::DelRange(req, reply) => Self::handle_del_range(kv, req, reply).await,
+            Self::CountRange(req, reply) => Self::handle_count_range(kv, req, reply).await,
+            Self::Exists(req, reply) => Self::handle_exists(kv, req, reply).await,
+            Self::Type(req, reply) => Self::handle_type(kv, req, reply).await,
+            Self::Rename(req, reply) => Self::handle_rename(kv, req, reply).await,
+            Self::Copy(req, reply) => Self::handle_copy(kv, req, reply).await,
+            Self::SPop(req, reply) => grown = Some(Self::handle_spop(kv, rng, req, reply).await),
+            Self::SRandMember(req, reply) => Self::handle_srandmember(kv, rng, req, reply).await,
+            Self::RandomKey(req, reply) => {
+                Self::handle_random_key(kv, ns, memory, rng, req, reply).await
+            }
+            Self::FlushDb(req, reply) => Self::handle_flush_db(kv, req, reply).await,
+            Self::DbSize(req, reply) => Self::handle_db_size(kv, req, reply).await,
+            Self::Move(req, reply) => Self::handle_move(dbs, ns, req, reply).await,
+            Self::SlowLogGet(req, reply) => Self::handle_slowlog_get(dbs, req, reply).await,
+            Self::SlowLogReset(req, reply) => Self::handle_slowlog_reset(dbs, req, reply).await,
+            Self::Info(_, reply) => Self::handle_stats(dbs, memory, reply).await,
+            Self::Stats(reply) => Self::handle_stats(dbs, memory, reply).await,
+        }
+        dbs.cleanup(ns);
+        grown
+    }
+}
+
+impl Req {
+    /// Handles the request; logs it if handled slower than the threshold.
+    pub async fn handle_logged(
+        self,
+        dbs: &mut Dbs,
+        ns: &str,
+        memory: &Memory,
+        rng: &mut StdRng,
+        conf: &Conf,
+    ) -> Option<isize> {
+        if 0 == conf.slowlog_max_len {
+            return self.handle(dbs, ns, memory, rng, conf).await;
+        }
+        let command: &'static str = self.command();
+        // Copies the bounded prefix of the key; the request is consumed by the handler.
+        let first_key: &[u8] = self.first_key();
+        let key: Vec<u8> = first_key[..first_key.len().min(SLOWLOG_KEY_MAX)].to_vec();
+        let arg_bytes: usize = self.arg_bytes();
+        let start: SystemTime = SystemTime::now();
+        let started: Instant = Instant::now();
+        let grown: Option<isize> = self.handle(dbs, ns, memory, rng, conf).await;
+        let duration: Duration = started.elapsed();
+        if conf.slowlog_threshold <= duration {
+            let entry = SlowEntry {
+                id: 0,
+                start,
+                duration,
+                command,
+                ns: ns.into(),
+                key,
+                arg_bytes,
+            };
+            dbs.slowlog_mut().push(entry, conf.slowlog_max_len);
+        }
+        grown
+    }
+}
+
+impl Req {
+    pub async fn handle_flush_db(
+        kv: &mut BTreeMap<Vec<u8>, Val>,
+        _req: FlushDbRequest,
+        reply: Sender<Result<FlushDbResponse, Status>>,
+    ) {
+        let sz: usize = kv.len();
+        kv.clear();
+        let res: Result<FlushDbResponse, Status> = Ok(FlushDbResponse {
+            count: sz as u64,
+            flush_time: Some(SystemTime::now().into()),
+        });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_stats(dbs: &Dbs, memory: &Memory, reply: Sender<Result<Stats, Status>>) {
+        match reply.send(Ok(dbs.stats(memory))).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_slowlog_get(
+        dbs: &Dbs,
+        req: SlowLogGetRequest,
+        reply: Sender<Result<SlowLogGetResponse, Status>>,
+    ) {
+        let slowlog: &SlowLog = dbs.slowlog();
+        let count: usize = usize::try_from(req.count).unwrap_or(usize::MAX);
+        let entries: Vec<SlowLogEntry> = slowlog
+            .newest(count)
+            .map(|e: &SlowEntry| SlowLogEntry {
+                id: e.id,
+                start_time: Some(e.start.into()),
+                duration: prost_types::Duration::try_from(e.duration).ok(),
+                command: e.command.into(),
+                namespace: e.ns.clone(),
+                key: e.key.clone(),
+                arg_bytes: e.arg_bytes as u64,
+            })
+            .collect();
+        let res: Result<SlowLogGetResponse, Status> = Ok(SlowLogGetResponse {
+            entries,
+            length: slowlog.len() as u64,
+        });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_slowlog_reset(
+        dbs: &mut Dbs,
+        _req: SlowLogResetRequest,
+        reply: Sender<Result<SlowLogResetResponse, Status>>,
+    ) {
+        let sz: usize = dbs.slowlog_mut().reset();
+        let res: Result<SlowLogResetResponse, Status> =
+            Ok(SlowLogResetResponse { count: sz as u64 });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_db_size(
+        kv: &BTreeMap<Vec<u8>, Val>,
+        _req: DbSizeRequest,
+        reply: Sender<Result<DbSizeResponse, Status>>,
+    ) {
+        let sz: usize = kv.len();
+        let res: Result<DbSizeResponse, Status> = Ok(DbSizeResponse { count: sz as u64 });
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+
+    pub async fn handle_move(
+        dbs: &mut Dbs,
+        ns: &str,
+        req: MoveRequest,
+        reply: Sender<Result<MoveResponse, Status>>,
+    ) {
+        let key: Vec<u8> = req.key;
+        let dst: String = req.namespace;
+        let res: Result<MoveResponse, Status> = (|| {
+            match ns == dst {
+                true => Err(Status::invalid_argument("the same namespace")),
+                false => Ok(()),
+            }?;
+            match dbs.get_mut(ns).kv.contains_key(&key) {
+                true => Ok(()),
+                false => Err(Status::not_found("no value found")),
+            }?;
+            let moved: bool = req.replace || !dbs.get_mut(&dst).kv.contains_key(&key);
+            if moved {
+                if let Some(v) = dbs.get_mut(ns).kv.remove(&key) {
+                    dbs.get_mut(&dst).kv.insert(key, v);
+                }
+            }
+            Ok(MoveResponse {
+                moved,
+                move_time: Some(SystemTime::now().into()),
+            })
+        })();
+        dbs.cleanup(&dst);
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => error!("{e}"),
+        }
+    }
+}
+
+/// Gets the range to check; any key if the bounds are invalid.
+pub fn range_access(lower: Option<RBound>, upper: Option<RBound>) -> Access {
+    let bounds: Result<_, Status> =
+        bound_convert(lower).and_then(|l| bound_convert(upper).map(|u| (l, u)));
+    match bounds {
+        Ok((l, u)) => Access::Range(l, u),
+        Err(_) => Access::All,
+    }
+}
+
+impl Req {
+    /// Gets the name of the rpc.
+    pub fn command(&self) -> &'static str {
+        match self {
+            Self::Del(..) => "Del",
+            Self::Range(..) => "Range",
+            Self::Scan(..) => "Scan",
+            Self::Set(..) => "Set",
+            Self::Get(..) => "Get",
+            Self::DGet(..) => "DGet",
+            Self::DHas(..) => "DHas",
+            Self::DSet(..) => "DSet",
+            Self::Pop(..) => "Pop",
+            Self::Push(..) => "Push",
+            Self::QLen(..) => "QLen",
+            Self::SAdd(..) => "SAdd",
+            Self::SDel(..) => "SDel",
+            Self::SLen(..) => "SLen",
+            Self::Incr(..) => "Incr",
+            Self::IncrBy(..) => "IncrBy",
+            Self::IncrByFloat(..) => "IncrByFloat",
+            Self::DIncrBy(..) => "DIncrBy",
+            Self::Append(..) => "Append",
+            Self::StrLen(..) => "StrLen",
+            Self::GetRange(..) => "GetRange",
+            Self::SetRange(..) => "SetRange",
+            Self::Acquire(..) => "Acquire",
+            Self::Renew(..) => "Renew",
+            Self::Release(..) => "Release",
+            Self::DelRange(..) => "DelRange",
+            Self::CountRange(..) => "CountRange",
+            Self::Exists(..) => "Exists",
+            Self::Type(..) => "Type",
+            Self::Rename(..) => "Rename",
+            Self::Copy(..) => "Copy",
+            Self::SPop(..) => "SPop",
+            Self::SRandMember(..) => "SRandMember",
+            Self::RandomKey(..) => "RandomKey",
+            Self::FlushDb(..) => "FlushDb",
+            Self::DbSize(..) => "DbSize",
+            Self::Move(..) => "Move",
+            Self::SlowLogGet(..) => "SlowLogGet",
+            Self::SlowLogReset(..) => "SlowLogReset",
+            Self::Info(..) => "Info",
+            Self::Stats(..) => "Stats",
+        }
+    }
+
+    /// Gets the keys to check the access.
+    pub fn access(&self) -> Access {
+        match self {
+            Self::Set(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::Get(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::DGet(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::DHas(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::DSet(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::Pop(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::Push(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::QLen(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::SAdd(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::SDel(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::SLen(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::Incr(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::IncrBy(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::IncrByFloat(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::DIncrBy(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::Append(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::StrLen(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::GetRange(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::SetRange(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::Type(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::SPop(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::SRandMember(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::Move(q, _) => Access::Keys(vec![q.key.clone()]),
+            Self::Acquire(q, _) => Access::Keys(vec![q.name.clone()]),
+            Self::Renew(q, _) => Access::Keys(vec![q.name.clone()]),
+            Self::Release(q, _) => Access::Keys(vec![q.name.clone()]),
+            Self::Del(q, _) => match q.keys.is_empty() {
+                true => Access::Keys(vec![q.key.clone()]),
+                false => Access::Keys(q.keys.clone()),
+            },
+            Self::Exists(q, _) => Access::Keys(q.keys.clone()),
+            Self::Rename(q, _) => Access::Keys(vec![q.key.clone(), q.new_key.clone()]),
+            Self::Copy(q, _) => Access::Keys(vec![q.key.clone(), q.new_key.clone()]),
+            Self::Range(q, _) => range_access(q.lower.clone(), q.upper.clone()),
+            Self::DelRange(q, _) => range_access(q.lower.clone(), q.upper.clone()),
+            Self::CountRange(q, _) => range_access(q.lower.clone(), q.upper.clone()),
+            Self::Scan(chunk, _) => {
+                let (lower, upper) = chunk.pattern.range(None);
+                Access::Range(lower, upper)
+            }
+            Self::RandomKey(..)
+            | Self::FlushDb(..)
+            | Self::DbSize(..)
+            | Self::SlowLogGet(..)
+            | Self::SlowLogReset(..)
+            | Self::Info(..)
+            | Self::Stats(..) => Access::All,
+        }
+    }
+
+    /// Gets the namespace written other than the caller's; the acl checks both.
+    pub fn destination(&self) -> Option<&str> {
+        match self {
+            Self::Move(q, _) => Some(q.namespace.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Borrows the first key or the lower bound of the range; empty if none.
+    pub fn first_key(&self) -> &[u8] {
+        match self {
+            Self::Set(q, _) => &q.key,
+            Self::Get(q, _) => &q.key,
+            Self::DGet(q, _) => &q.key,
+            Self::DHas(q, _) => &q.key,
+            Self::DSet(q, _) => &q.key,
+            Self::Pop(q, _) => &q.key,
+            Self::Push(q, _) => &q.key,
+            Self::QLen(q, _) => &q.key,
+            Self::SAdd(q, _) => &q.key,
+            Self::SDel(q, _) => &q.key,
+            Self::SLen(q, _) => &q.key,
+            Self::Incr(q, _) => &q.key,
+            Self::IncrBy(q, _) => &q.key,
+            Self::IncrByFloat(q, _) => &q.key,
+            Self::DIncrBy(q, _) => &q.key,
+            Self::Append(q, _) => &q.key,
+            Self::StrLen(q, _) => &q.key,
+            Self::GetRange(q, _) => &q.key,
+            Self::SetRange(q, _) => &q.key,
+            Self::Type(q, _) => &q.key,
+            Self::SPop(q, _) => &q.key,
+            Self::SRandMember(q, _) => &q.key,
+            Self::Move(q, _) => &q.key,
+            Self::Rename(q, _) => &q.key,
+            Self::Copy(q, _) => &q.key,
+            Self::Acquire(q, _) => &q.name,
+            Self::Renew(q, _) => &q.name,
+            Self::Release(q, _) => &q.name,
+            Self::Del(q, _) => q.keys.first().unwrap_or(&q.key),
+            Self::Exists(q, _) => q.keys.first().map(Vec::as_slice).unwrap_or_default(),
+            Self::Range(q, _) => bound_key(&q.lower),
+            Self::DelRange(q, _) => bound_key(&q.lower),
+            Self::CountRange(q, _) => bound_key(&q.lower),
+            Self::Scan(chunk, _) => chunk.after.as_deref().unwrap_or_default(),
+            Self::RandomKey(..)
+            | Self::FlushDb(..)
+            | Self::DbSize(..)
+            | Self::SlowLogGet(..)
+            | Self::SlowLogReset(..)
+            | Self::Info(..)
+            | Self::Stats(..) => &[],
+        }
+    }
+
+    /// Sums the bytes of the keys, the members and the encoded values.
+    pub fn arg_bytes(&self) -> usize {
+        match self {
+            Self::Set(q, _) => q.key.len() + q.value.as_ref().map_or(0, Message::encoded_len),
+            Self::DSet(q, _) => {
+                q.key.len() + q.dkey.len() + q.value.as_ref().map_or(0, Message::encoded_len)
+            }
+            Self::Push(q, _) => {
+                q.key.len()
+                    + q.value
+                        .iter()
+                        .chain(&q.values)
+                        .map(Message::encoded_len)
+                        .sum::<usize>()
+            }
+            Self::Del(q, _) => match q.keys.is_empty() {
+                true => q.key.len(),
+                false => q.keys.iter().map(Vec::len).sum(),
+            },
+            Self::Exists(q, _) => q.keys.iter().map(Vec::len).sum(),
+            Self::DGet(q, _) => q.key.len() + q.dkey.len(),
+            Self::DHas(q, _) => q.key.len() + q.dkey.len(),
+            Self::DIncrBy(q, _) => q.key.len() + q.dkey.len(),
+            Self::SAdd(q, _) => q.key.len() + q.val.len(),
+            Self::SDel(q, _) => q.key.len() + q.val.len(),
+            Self::Append(q, _) => q.key.len() + q.value.len(),
+            Self::SetRange(q, _) => q.key.len() + q.value.len(),
+            Self::Rename(q, _) => q.key.len() + q.new_key.len(),
+            Self::Copy(q, _) => q.key.len() + q.new_key.len(),
+            Self::Range(q, _) => bound_key(&q.lower).len() + bound_key(&q.upper).len(),
+            Self::DelRange(q, _) => bound_key(&q.lower).len() + bound_key(&q.upper).len(),
+            Self::CountRange(q, _) => bound_key(&q.lower).len() + bound_key(&q.upper).len(),
+            Self::Get(..)
+            | Self::Pop(..)
+            | Self::QLen(..)
+            | Self::SLen(..)
+            | Self::Incr(..)
+            | Self::IncrBy(..)
+            | Self::IncrByFloat(..)
+            | Self::StrLen(..)
+            | Self::GetRange(..)
+            | Self::Type(..)
+            | Self::SPop(..)
+            | Self::SRandMember(..)
+            | Self::Move(..)
+            | Self::Acquire(..)
+            | Self::Renew(..)
+            | Self::Release(..)
+            | Self::Scan(..)
+            | Self::RandomKey(..)
+            | Self::FlushDb(..)
+            | Self::DbSize(..)
+            | Self::SlowLogGet(..)
+            | Self::SlowLogReset(..)
+            | Self::Info(..)
+            | Self::Stats(..) => self.first_key().len(),
+        }
+    }
+}
+
+/// Borrows the key of the bound; empty if unbounded.
+pub fn bound_key(ob: &Option<RBound>) -> &[u8] {
+    match ob.as_ref().and_then(|b| b.bound.as_ref()) {
+        Some(IBound::Included(k) | IBound::Excluded(k)) => k,
+        Some(IBound::Unbounded(_)) | None => &[],
+    }
+}
+
+/// Sends the error instead of handling the request.
+pub async fn reply_err<T>(reply: Sender<Result<T, Status>>, e: Status) {
+    match reply.send(Err(e)).await {
+        Ok(_) => {}
+        Err(e) => error!("{e}"),
+    }
+}
+
+impl Req {
+    /// Checks the request may use more memory.
+    pub fn may_grow(&self) -> bool {
+        matches!(
+            self,
+            Self::Set(..)
+                | Self::DSet(..)
+                | Self::Push(..)
+                | Self::SAdd(..)
+                | Self::Incr(..)
+                | Self::IncrBy(..)
+                | Self::IncrByFloat(..)
+                | Self::DIncrBy(..)
+                | Self::Append(..)
+                | Self::SetRange(..)
+                | Self::Copy(..)
+        )
+    }
+
+    pub fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Self::Del(..)
+                | Self::Set(..)
+                | Self::DSet(..)
+                | Self::Pop(..)
+                | Self::Push(..)
+                | Self::SAdd(..)
+                | Self::SDel(..)
+                | Self::Incr(..)
+                | Self::IncrBy(..)
+                | Self::IncrByFloat(..)
+                | Self::DIncrBy(..)
+                | Self::Append(..)
+                | Self::SetRange(..)
+                | Self::DelRange(..)
+                | Self::Rename(..)
+                | Self::Copy(..)
+                | Self::SPop(..)
+                | Self::FlushDb(..)
+                | Self::Move(..)
+        )
+    }
+
+    /// Forgets the keys the request removes at once; no key is copied.
+    pub fn forget(&self, ns: &str, kv: &BTreeMap<Vec<u8>, Val>, memory: &mut Memory) {
+        match self {
+            Self::FlushDb(..) => memory.remove_ns(ns),
+            Self::DelRange(q, _) => {
+                let bounds: Result<_, Status> = bound_convert(q.lower.clone())
+                    .and_then(|l| bound_convert(q.upper.clone()).map(|u| (l, u)));
+                if let Ok((l, u)) = bounds {
+                    if check_bound(&l, &u).is_ok() && range_nonempty(&l, &u) {
+                        for key in kv.range((l, u)).map(|pair| pair.0) {
+                            memory.remove(ns, key);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Gets the keys accessed by the request.
+    pub fn slots(&self, ns: &str) -> Vec<Slot> {
+        let keys: Vec<Vec<u8>> = match self {
+            Self::Set(q, _) => vec![q.key.clone()],
+            Self::Get(q, _) => vec![q.key.clone()],
+            Self::DGet(q, _) => vec![q.key.clone()],
+            Self::DHas(q, _) => vec![q.key.clone()],
+            Self::DSet(q, _) => vec![q.key.clone()],
+            Self::Pop(q, _) => vec![q.key.clone()],
+            Self::Push(q, _) => vec![q.key.clone()],
+            Self::QLen(q, _) => vec![q.key.clone()],
+            Self::SAdd(q, _) => vec![q.key.clone()],
+            Self::SDel(q, _) => vec![q.key.clone()],
+            Self::SLen(q, _) => vec![q.key.clone()],
+            Self::Incr(q, _) => vec![q.key.clone()],
+            Self::IncrBy(q, _) => vec![q.key.clone()],
+            Self::IncrByFloat(q, _) => vec![q.key.clone()],
+            Self::DIncrBy(q, _) => vec![q.key.clone()],
+            Self::Append(q, _) => vec![q.key.clone()],
+            Self::StrLen(q, _) => vec![q.key.clone()],
+            Self::GetRange(q, _) => vec![q.key.clone()],
+            Self::SetRange(q, _) => vec![q.key.clone()],
+            Self::SPop(q, _) => vec![q.key.clone()],
+            Self::SRandMember(q, _) => vec![q.key.clone()],
+            Self::Del(q, _) => match q.keys.is_empty() {
+                true => vec![q.key.clone()],
+                false => q.keys.clone(),
+            },
+            Self::Rename(q, _) => vec![q.key.clone(), q.new_key.clone()],
+            Self::Copy(q, _) => vec![q.key.clone(), q.new_key.clone()],
+            Self::Move(q, _) => {
+                return vec![
+                    (ns.into(), q.key.clone()),
+                    (q.namespace.clone(), q.key.clone()),
+                ]
+            }
+            // Forgotten before removed; see forget.
+            Self::DelRange(..) | Self::FlushDb(..) => vec![],
+            Self::Range(..)
+            | Self::Scan(..)
+            | Self::Acquire(..)
+            | Self::Renew(..)
+            | Self::Release(..)
+            | Self::CountRange(..)
+            | Self::Exists(..)
+            | Self::Type(..)
+            | Self::RandomKey(..)
+            | Self::DbSize(..)
+            | Self::SlowLogGet(..)
+            | Self::SlowLogReset(..)
+            | Self::Info(..)
+            | Self::Stats(..) => vec![],
+        };
+        keys.into_iter().map(|k| (ns.into(), k)).collect()
+    }
+
+    pub async fn reject(self, e: Status) {
+        match self {
+            Self::Del(_, reply) => reply_err(reply, e).await,
+            Self::Range(_, reply) => {
+                let (tx, rx) = tokio::sync::mpsc::channel(1);
+                match tx.try_send(Err(e)) {
+                    Ok(_) => {}
+                    Err(e) => error!("{e}"),
+                }
+                match reply.send(rx).await {
+                    Ok(_) => {}
+                    Err(e) => error!("{e}"),
+                }
+            }
+            Self::Scan(_, reply) => reply_err(reply, e).await,
+            Self::Set(_, reply) => reply_err(reply, e).await,
+            Self::Get(_, reply) => reply_err(reply, e).await,
+            Self::DGet(_, reply) => reply_err(reply, e).await,
+            Self::DHas(_, reply) => reply_err(reply, e).await,
+            Self::DSet(_, reply) => reply_err(reply, e).await,
+            Self::Pop(_, reply) => reply_err(reply, e).await,
+            Self::Push(_, reply) => reply_err(reply, e).await,
+            Self::QLen(_, reply) => reply_err(reply, e).await,
+            Self::SAdd(_, reply) => reply_err(reply, e).await,
+            Self::SDel(_, reply) => reply_err(reply, e).await,
+            Self::SLen(_, reply) => reply_err(reply, e).await,
+            Self::Incr(_, reply) => reply_err(reply, e).await,
+            Self::IncrBy(_, reply) => reply_err(reply, e).await,
+            Self::IncrByFloat(_, reply) => reply_err(reply, e).await,
+            Self::DIncrBy(_, reply) => reply_err(reply, e).await,
+            Self::Append(_, reply) => reply_err(reply, e).await,
+            Self::StrLen(_, reply) => reply_err(reply, e).await,
+            Self::GetRange(_, reply) => reply_err(reply, e).await,
+            Self::SetRange(_, reply) => reply_err(reply, e).await,
+            Self::Acquire(_, reply) => reply_err(reply, e).await,
+            Self::Renew(_, reply) => reply_err(reply, e).await,
+            Self::Release(_, reply) => reply_err(reply, e).await,
+            Self::DelRange(_, reply) => reply_err(reply, e).await,
+            Self::CountRange(_, reply) => reply_err(reply, e).await,
+            Self::Exists(_, reply) => reply_err(reply, e).await,
+            Self::Type(_, reply) => reply_err(reply, e).await,
+            Self::Rename(_, reply) => reply_err(reply, e).await,
+            Self::Copy(_, reply) => reply_err(reply, e).await,
+            Self::SPop(_, reply) => reply_err(reply, e).await,
+            Self::SRandMember(_, reply) => reply_err(reply, e).await,
+            Self::RandomKey(_, reply) => reply_err(reply, e).await,
+            Self::FlushDb(_, reply) => reply_err(reply, e).await,
+            Self::DbSize(_, reply) => reply_err(reply, e).await,
+            Self::Move(_, reply) => reply_err(reply, e).await,
+            Self::SlowLogGet(_, reply) => reply_err(reply, e).await,
+            Self::SlowLogReset(_, reply) => reply_err(reply, e).await,
+            Self::Info(_, reply) => reply_err(reply, e).await,
+            Self::Stats(reply) => reply_err(reply, e).await,
+        }
+    }
+}
+
+/// Evicts the keys until the memory used gets below the limit; fails if no key can be evicted.
+pub fn memory_reserve(
+    dbs: &mut Dbs,
+    memory: &mut Memory,
+    rng: &mut StdRng,
+    conf: &Conf,
+) -> Result<(), Status> {
+    while conf.max_memory < memory.used() {
+        let victim: Slot = memory
+            .victim(conf.eviction, conf.eviction_samples, rng)
+            .ok_or_else(|| Status::resource_exhausted("the memory limit exceeded"))?;
+        let (ns, key) = &victim;
+        dbs.evict(ns, key);
+        memory.remove(ns, key);
+    }
+    Ok(())
+}
+
+impl Envelope {
+    /// Wraps the request traced in the current span.
+    pub fn new(ns: String, req: Req) -> Self {
+        Self {
+            ns,
+            req,
+            span: Span::current(),
+            queued: info_span!("queue"),
+        }
+    }
+
+    pub async fn handle(self, dbs: &mut Dbs, memory: &mut Memory, rng: &mut StdRng, conf: &Conf) {
+        let Self {
+            ns,
+            req,
+            span,
+            queued,
+        } = self;
+        drop(queued);
+        if !matches!(req, Req::Stats(..)) {
+            dbs.count(req.command());
+        }
+        let handling: Span = info_span!(parent: &span, "handle", command = req.command());
+        Self::handle_req(ns, req, dbs, memory, rng, conf)
+            .instrument(handling)
             .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
+    }
+
+    async fn handle_req(
+        ns: String,
+        req: Req,
+        dbs: &mut Dbs,
+        memory: &mut Memory,
+        rng: &mut StdRng,
+        conf: &Conf,
+    ) {
+        // The writes are tracked even without the limit to keep the statistics.
+        let limited: bool = 0 < conf.max_memory;
+        if limited && req.may_grow() {
+            match memory_reserve(dbs, memory, rng, conf) {
+                Ok(_) => {}
+                Err(e) => return req.reject(e).await,
+            }
+        }
+        let write: bool = req.is_write();
+        if let (true, Some(db)) = (write, dbs.get(&ns)) {
+            req.forget(&ns, &db.kv, memory);
+        }
+        let slots: Vec<Slot> = match write || limited {
+            true => req.slots(&ns),
+            false => vec![],
+        };
+        let grown: Option<isize> = req.handle_logged(dbs, &ns, memory, rng, conf).await;
+        for (sns, key) in slots {
+            let found: Option<&Val> = dbs.get(&sns).and_then(|db| db.kv.get(&key));
+            match (write, found, grown) {
+                (false, _, _) => memory.touch(&sns, &key),
+                (true, None, _) => memory.remove(&sns, &key),
+                (true, Some(v), Some(delta)) => memory.grow(&sns, &key, v, delta),
+                (true, Some(v), None) => memory.resize(&sns, &key, Usage::new(&key, v)),
+            }
+        }
+    }
+}
+
+/// The result of an operation sent to the actor.
+pub type Pending = Pin<Box<dyn Future<Output = Result<ExecResult, Status>> + Send>>;
+
+/// An operation of the Execute stream waiting its result; observed and traced once resolved.
+pub struct InFlight {
+    id: u64,
+    command: Option<&'static str>,
+    started: Instant,
+    span: Span,
+    pending: Pending,
+}
+
+pub fn pending_err(e: Status) -> Pending {
+    Box::pin(futures::future::ready(Err(e)))
+}
+
+/// Creates the request for the actor and the future which waits its result.
+pub fn dispatch<Q, R>(
+    q: Q,
+    wrap: fn(Q, Sender<Result<R, Status>>) -> Req,
+    done: fn(R) -> ExecResult,
+) -> (Req, Pending)
+where
+    R: Send + 'static,
+{
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let pending: Pending = Box::pin(async move {
+        let ores: Option<_> = rx.recv().await;
+        let rslt: Result<R, Status> = ores.ok_or_else(|| Status::internal("no response got"))?;
+        rslt.map(done)
+    });
+    (wrap(q, tx), pending)
+}
+
+pub fn dispatch_range(q: RangeRequest) -> (Req, Pending) {
+    let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+    let pending: Pending = Box::pin(async move {
         let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: GetResponse = rslt?;
+        let rcv: Receiver<Result<RangeResponse, Status>> =
+            ores.ok_or_else(|| Status::internal("no response got"))?;
+        let items: Vec<RangeResponse> = ReceiverStream::new(rcv).try_collect().await?;
+        Ok(ExecResult::Range(RangeResponseList { items }))
+    });
+    (Req::Range(q, tx), pending)
+}
+
+impl Req {
+    pub fn from_op(op: Op) -> (Self, Pending) {
+        match op {
+            Op::Set(q) => dispatch(q, Self::Set, ExecResult::Set),
+            Op::Get(q) => dispatch(q, Self::Get, ExecResult::Get),
+            Op::Push(q) => dispatch(q, Self::Push, ExecResult::Push),
+            Op::Pop(q) => dispatch(q, Self::Pop, ExecResult::Pop),
+            Op::QLen(q) => dispatch(q, Self::QLen, ExecResult::QLen),
+            Op::DSet(q) => dispatch(q, Self::DSet, ExecResult::DSet),
+            Op::DGet(q) => dispatch(q, Self::DGet, ExecResult::DGet),
+            Op::DHas(q) => dispatch(q, Self::DHas, ExecResult::DHas),
+            Op::SAdd(q) => dispatch(q, Self::SAdd, ExecResult::SAdd),
+            Op::SDel(q) => dispatch(q, Self::SDel, ExecResult::SDel),
+            Op::SLen(q) => dispatch(q, Self::SLen, ExecResult::SLen),
+            Op::Del(q) => dispatch(q, Self::Del, ExecResult::Del),
+            Op::Range(q) => dispatch_range(q),
+            Op::Incr(q) => dispatch(q, Self::Incr, ExecResult::Incr),
+            Op::IncrBy(q) => dispatch(q, Self::IncrBy, ExecResult::IncrBy),
+            Op::IncrByFloat(q) => dispatch(q, Self::IncrByFloat, ExecResult::IncrByFloat),
+            Op::DIncrBy(q) => dispatch(q, Self::DIncrBy, ExecResult::DIncrBy),
+            Op::Append(q) => dispatch(q, Self::Append, ExecResult::Append),
+            Op::StrLen(q) => dispatch(q, Self::StrLen, ExecResult::StrLen),
+            Op::GetRange(q) => dispatch(q, Self::GetRange, ExecResult::GetRange),
+            Op::SetRange(q) => dispatch(q, Self::SetRange, ExecResult::SetRange),
+            Op::DelRange(q) => dispatch(q, Self::DelRange, ExecResult::DelRange),
+            Op::CountRange(q) => dispatch(q, Self::CountRange, ExecResult::CountRange),
+            Op::Exists(q) => dispatch(q, Self::Exists, ExecResult::Exists),
+            Op::Type(q) => dispatch(q, Self::Type, ExecResult::Type),
+            Op::Rename(q) => dispatch(q, Self::Rename, ExecResult::Rename),
+            Op::Copy(q) => dispatch(q, Self::Copy, ExecResult::Copy),
+            Op::SPop(q) => dispatch(q, Self::SPop, ExecResult::SPop),
+            Op::SRandMember(q) => dispatch(q, Self::SRandMember, ExecResult::SRandMember),
+            Op::RandomKey(q) => dispatch(q, Self::RandomKey, ExecResult::RandomKey),
+            Op::FlushDb(q) => dispatch(q, Self::FlushDb, ExecResult::FlushDb),
+            Op::DbSize(q) => dispatch(q, Self::DbSize, ExecResult::DbSize),
+            Op::Move(q) => dispatch(q, Self::Move, ExecResult::Move),
+        }
+    }
+}
+
+/// Sends the operations to the actor without waiting for their results.
+pub async fn execute_read(
+    mut incoming: Streaming<ExecuteRequest>,
+    svc: ChanSvc,
+    caller: Caller,
+    pending: Sender<Result<InFlight, Status>>,
+) {
+    let mut closing: watch::Receiver<bool> = svc.closing.clone();
+    // Traces the operations as the children of the Execute span.
+    let caller = Caller {
+        parent: Span::current().context(),
+        ..caller
+    };
+    loop {
+        let next: Result<Option<ExecuteRequest>, Status> = tokio::select! {
+            next = incoming.message() => next,
+            // Ends the stream after the pending results written.
+            _ = closed(&mut closing) => return,
+        };
+        let item: Result<InFlight, Status> = match next {
+            Ok(None) => return,
+            Err(e) => Err(e),
+            Ok(Some(ereq)) => {
+                let id: u64 = ereq.id;
+                let started: Instant = Instant::now();
+                match ereq.op {
+                    None => Ok(InFlight {
+                        id,
+                        command: None,
+                        started,
+                        span: Span::none(),
+                        pending: pending_err(Status::invalid_argument("no op specified")),
+                    }),
+                    Some(op) => {
+                        let (req, p) = Req::from_op(op);
+                        let command: Option<&'static str> = Some(req.command());
+                        let span: Span = rpc_span(req.command(), &caller);
+                        let sent = svc.send(caller.clone(), req).instrument(span.clone());
+                        let pending: Pending = match sent.await {
+                            Ok(_) => p,
+                            Err(e) => pending_err(e),
+                        };
+                        Ok(InFlight {
+                            id,
+                            command,
+                            started,
+                            span,
+                            pending,
+                        })
+                    }
+                }
+            }
+        };
+        let stop: bool = item.is_err();
+        match pending.send(item).await {
+            Ok(_) => {}
+            Err(_) => return,
+        }
+        if stop {
+            return;
+        }
+    }
+}
+
+/// Waits the results in the order of the operations; observes and traces each operation.
+pub async fn execute_write(
+    svc: ChanSvc,
+    mut pending: Receiver<Result<InFlight, Status>>,
+    reply: Sender<Result<ExecuteResponse, Status>>,
+) {
+    loop {
+        let oitem: Option<Result<InFlight, Status>> = pending.recv().await;
+        let res: Result<ExecuteResponse, Status> = match oitem {
+            None => return,
+            Some(Err(e)) => Err(e),
+            Some(Ok(op)) => {
+                let id: u64 = op.id;
+                let rslt: Result<ExecResult, Status> = op.pending.instrument(op.span.clone()).await;
+                span_record(&op.span, &rslt);
+                if let Some(command) = op.command {
+                    svc.observe(command, &rslt, op.started);
+                }
+                let result: ExecResult = rslt.unwrap_or_else(|e| {
+                    ExecResult::Error(ExecuteError {
+                        code: e.code() as i32,
+                        message: e.message().into(),
+                    })
+                });
+                Ok(ExecuteResponse {
+                    id,
+                    result: Some(result),
+                })
+            }
+        };
+        let stop: bool = res.is_err();
+        match reply.send(res).await {
+            Ok(_) => {}
+            Err(e) => {
+                warn!("the client gone: {e}");
+                return;
+            }
+        }
+        if stop {
+            return;
+        }
+    }
+}
+
+/// Waits until closed; never completes if the closer dropped without closing.
+pub async fn closed(closing: &mut watch::Receiver<bool>) {
+    let closed: bool = closing.wait_for(|c| *c).await.is_ok();
+    if !closed {
+        futures::future::pending::<()>().await
+    }
+}
+
+/// Decrements the counter when dropped; the send may be cancelled.
+struct Blocked<'a>(&'a AtomicUsize);
+
+impl Drop for Blocked<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, atomic::Ordering::Relaxed);
+    }
+}
+
+/// Sends the requests to the actor; counts the senders blocked since the channel holds one request.
+#[derive(Clone)]
+pub struct Queue {
+    sender: Sender<Envelope>,
+    blocked: Arc<AtomicUsize>,
+}
+
+impl Queue {
+    pub fn new(sender: Sender<Envelope>) -> Self {
+        Self {
+            sender,
+            blocked: Arc::default(),
+        }
+    }
+
+    pub async fn send(&self, env: Envelope) -> Result<(), Status> {
+        self.blocked.fetch_add(1, atomic::Ordering::Relaxed);
+        let _blocked = Blocked(&self.blocked);
+        self.sender
+            .send(env)
+            .await
+            .map_err(|e| Status::internal(format!("unable to send: {e}")))
+    }
+
+    /// Gets the number of the requests queued and the senders waiting to queue.
+    pub fn depth(&self) -> usize {
+        let queued: usize = self.sender.max_capacity() - self.sender.capacity();
+        self.blocked.load(atomic::Ordering::Relaxed) + queued
+    }
+}
+
+#[derive(Clone)]
+pub struct ChanSvc {
+    sender: Queue,
+    acl: Arc<Acl>,
+    closing: watch::Receiver<bool>,
+    metrics: Option<Arc<Metrics>>,
+    clients: Arc<Clients>,
+    config: InfoConfig,
+    started: Instant,
+    start_time: SystemTime,
+}
+
+impl ChanSvc {
+    /// Rejects the new requests while closing.
+    pub fn check_open(&self) -> Result<(), Status> {
+        match *self.closing.borrow() {
+            true => Err(Status::unavailable("shutting down")),
+            false => Ok(()),
+        }
+    }
+
+    /// Checks the caller is allowed the command over the keys in the namespace if the acl is enabled.
+    pub fn permit<F>(
+        &self,
+        caller: &Caller,
+        ns: &str,
+        command: &str,
+        access: F,
+    ) -> Result<(), Status>
+    where
+        F: FnOnce() -> Access,
+    {
+        match self.acl.is_enabled() {
+            true => self.acl.check(&caller.principal, ns, command, &access()),
+            false => Ok(()),
+        }
+    }
+
+    /// Sends the request to the actor if the caller is allowed in both namespaces the request uses.
+    pub async fn send(&self, caller: Caller, req: Req) -> Result<(), Status> {
+        self.check_open()?;
+        self.permit(&caller, &caller.ns, req.command(), || req.access())?;
+        if let Some(dst) = req.destination() {
+            self.permit(&caller, dst, req.command(), || req.access())?;
+        }
+        let env = Envelope::new(caller.ns, req);
+        self.sender.send(env).await
+    }
+
+    pub fn observe<T>(&self, command: &str, rslt: &Result<T, Status>, started: Instant) {
+        if let Some(m) = &self.metrics {
+            m.observe(command, rslt, started.elapsed())
+        }
+    }
+
+    /// Counts the stream opened; the operations sent over it are observed one by one.
+    pub fn count<T>(&self, command: &str, rslt: &Result<T, Status>) {
+        if let Some(m) = &self.metrics {
+            m.count(command, rslt)
+        }
+    }
+
+    /// Observes the latency of a chunk of the stream counted by [`ChanSvc::count`].
+    pub fn time(&self, command: &str, started: Instant) {
+        if let Some(m) = &self.metrics {
+            m.time(command, started.elapsed())
+        }
+    }
+
+    /// Sends the request and waits the response.
+    pub async fn call<T>(
+        &self,
+        caller: Caller,
+        req: Req,
+        mut rx: Receiver<Result<T, Status>>,
+    ) -> Result<T, Status> {
+        let started: Instant = Instant::now();
+        let command: &'static str = req.command();
+        let span: Span = rpc_span(command, &caller);
+        let sent = async {
+            self.send(caller, req).await?;
+            rx.recv()
+                .await
+                .unwrap_or_else(|| Err(Status::internal("no response got")))
+        };
+        let rslt: Result<T, Status> = sent.instrument(span.clone()).await;
+        span_record(&span, &rslt);
+        self.observe(command, &rslt, started);
+        rslt
+    }
+
+    /// Gets the statistics bypassing the acl; used by the metrics.
+    pub async fn stats(&self) -> Result<Stats, Status> {
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let env = Envelope::new(String::new(), Req::Stats(tx));
+        self.sender.send(env).await?;
+        rx.recv()
+            .await
+            .unwrap_or_else(|| Err(Status::internal("no response got")))
+    }
+
+    /// Adds the values kept outside of the actor to the statistics.
+    pub fn info_new(&self, stats: Stats) -> InfoResponse {
+        let keys = InfoKeys {
+            total: stats.vars + stats.maps + stats.sets + stats.deqs,
+            vars: stats.vars,
+            maps: stats.maps,
+            sets: stats.sets,
+            deqs: stats.deqs,
+        };
+        InfoResponse {
+            version: env!("CARGO_PKG_VERSION").into(),
+            start_time: Some(self.start_time.into()),
+            uptime: prost_types::Duration::try_from(self.started.elapsed()).ok(),
+            config: Some(self.config.clone()),
+            keys: Some(keys),
+            elements: stats.elements,
+            memory: stats.memory,
+            evicted: stats.evicted,
+            expired: stats.expired,
+            commands: stats
+                .commands
+                .into_iter()
+                .map(|(command, n)| (command.into(), n))
+                .collect(),
+            connected_clients: self.clients.connected(),
+            persistence: Persistence::Disabled.into(),
+            role: ReplicationRole::Standalone.into(),
+        }
+    }
+
+    /// Gets the number of the requests queued to the actor.
+    pub fn queue_depth(&self) -> usize {
+        self.sender.depth()
+    }
+}
+
+#[tonic::async_trait]
+impl MemoryDatabaseService for ChanSvc {
+    type RangeStream = ReceiverStream<Result<RangeResponse, Status>>;
+    type ExecuteStream = ReceiverStream<Result<ExecuteResponse, Status>>;
+    type ScanStream = ReceiverStream<Result<ScanResponse, Status>>;
+
+    async fn set(
+        &self,
+        request: Request<SetRequest>,
+    ) -> std::result::Result<Response<SetResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: SetRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Set(iq, tx);
+        let res: SetResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn get(
+        &self,
+        request: Request<GetRequest>,
+    ) -> std::result::Result<Response<GetResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: GetRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Get(iq, tx);
+        let res: GetResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -554,16 +2853,11 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<PushRequest>,
     ) -> std::result::Result<Response<PushResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: PushRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::Push(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: PushResponse = rslt?;
+        let res: PushResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -571,16 +2865,11 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<PopRequest>,
     ) -> std::result::Result<Response<PopResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: PopRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::Pop(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: PopResponse = rslt?;
+        let res: PopResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -588,16 +2877,11 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<QLenRequest>,
     ) -> std::result::Result<Response<QLenResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: QLenRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::QLen(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: QLenResponse = rslt?;
+        let res: QLenResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -605,16 +2889,11 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<DSetRequest>,
     ) -> std::result::Result<Response<DSetResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: DSetRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
-        let req = Req::DSet(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: DSetResponse = rslt?;
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::DSet(iq, tx);
+        let res: DSetResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -622,16 +2901,11 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<DGetRequest>,
     ) -> std::result::Result<Response<DGetResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: DGetRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::DGet(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: DGetResponse = rslt?;
+        let res: DGetResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -639,32 +2913,22 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<DHasRequest>,
     ) -> std::result::Result<Response<DHasResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: DHasRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::DHas(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: DHasResponse = rslt?;
+        let res: DHasResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
     async fn s_add(
         &self,
         request: Request<SAddRequest>,
     ) -> std::result::Result<Response<SAddResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: SAddRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::SAdd(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: SAddResponse = rslt?;
+        let res: SAddResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -672,16 +2936,11 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<SDelRequest>,
     ) -> std::result::Result<Response<SDelResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: SDelRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::SDel(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: SDelResponse = rslt?;
+        let res: SDelResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -689,16 +2948,11 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<SLenRequest>,
     ) -> std::result::Result<Response<SLenResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: SLenRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::SLen(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: SLenResponse = rslt?;
+        let res: SLenResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -706,16 +2960,11 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<DelRequest>,
     ) -> std::result::Result<Response<DelResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: DelRequest = request.into_inner();
-        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
         let req = Req::Del(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rslt: Result<_, _> = ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: DelResponse = rslt?;
+        let res: DelResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
 
@@ -723,39 +2972,776 @@ impl MemoryDatabaseService for ChanSvc {
         &self,
         request: Request<RangeRequest>,
     ) -> std::result::Result<Response<Self::RangeStream>, Status> {
+        let caller: Caller = caller_get(&request)?;
         let iq: RangeRequest = request.into_inner();
+        let started: Instant = Instant::now();
+        let span: Span = rpc_span("Range", &caller);
         let (tx, mut rx) = tokio::sync::mpsc::channel(1);
         let req = Req::Range(iq, tx);
-        self.sender
-            .send(req)
-            .await
-            .map_err(|e| Status::internal(format!("unable to send: {e}")))?;
-        let ores: Option<_> = rx.recv().await;
-        let rcv: Receiver<Result<RangeResponse, Status>> =
-            ores.ok_or_else(|| Status::internal("no response got"))?;
-        let res: ReceiverStream<_> = ReceiverStream::new(rcv);
+        let sent = async {
+            self.send(caller, req).await?;
+            rx.recv()
+                .await
+                .ok_or_else(|| Status::internal("no response got"))
+        };
+        let rslt: Result<Receiver<Result<RangeResponse, Status>>, Status> =
+            sent.instrument(span.clone()).await;
+        span_record(&span, &rslt);
+        self.observe("Range", &rslt, started);
+        let res: ReceiverStream<_> = ReceiverStream::new(rslt?);
+        Ok(Response::new(res))
+    }
+
+    async fn incr(
+        &self,
+        request: Request<IncrRequest>,
+    ) -> std::result::Result<Response<IncrResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: IncrRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Incr(iq, tx);
+        let res: IncrResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn incr_by(
+        &self,
+        request: Request<IncrByRequest>,
+    ) -> std::result::Result<Response<IncrByResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: IncrByRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::IncrBy(iq, tx);
+        let res: IncrByResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn incr_by_float(
+        &self,
+        request: Request<IncrByFloatRequest>,
+    ) -> std::result::Result<Response<IncrByFloatResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: IncrByFloatRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::IncrByFloat(iq, tx);
+        let res: IncrByFloatResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn d_incr_by(
+        &self,
+        request: Request<DIncrByRequest>,
+    ) -> std::result::Result<Response<DIncrByResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: DIncrByRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::DIncrBy(iq, tx);
+        let res: DIncrByResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn append(
+        &self,
+        request: Request<AppendRequest>,
+    ) -> std::result::Result<Response<AppendResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: AppendRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Append(iq, tx);
+        let res: AppendResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn str_len(
+        &self,
+        request: Request<StrLenRequest>,
+    ) -> std::result::Result<Response<StrLenResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: StrLenRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::StrLen(iq, tx);
+        let res: StrLenResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn get_range(
+        &self,
+        request: Request<GetRangeRequest>,
+    ) -> std::result::Result<Response<GetRangeResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: GetRangeRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::GetRange(iq, tx);
+        let res: GetRangeResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn set_range(
+        &self,
+        request: Request<SetRangeRequest>,
+    ) -> std::result::Result<Response<SetRangeResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: SetRangeRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::SetRange(iq, tx);
+        let res: SetRangeResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn acquire(
+        &self,
+        request: Request<AcquireRequest>,
+    ) -> std::result::Result<Response<AcquireResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: AcquireRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Acquire(iq, tx);
+        let res: AcquireResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn renew(
+        &self,
+        request: Request<RenewRequest>,
+    ) -> std::result::Result<Response<RenewResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: RenewRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Renew(iq, tx);
+        let res: RenewResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn release(
+        &self,
+        request: Request<ReleaseRequest>,
+    ) -> std::result::Result<Response<ReleaseResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: ReleaseRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Release(iq, tx);
+        let res: ReleaseResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn scan(
+        &self,
+        request: Request<ScanRequest>,
+    ) -> std::result::Result<Response<Self::ScanStream>, Status> {
+        self.check_open()?;
+        let caller: Caller = caller_get(&request)?;
+        let iq: ScanRequest = request.into_inner();
+        let filter: ValType = ValType::try_from(iq.type_filter)
+            .map_err(|_| Status::invalid_argument("invalid type filter"))?;
+        let pattern: Pattern = Pattern::try_from(iq.pattern)?;
+        let (lower, upper) = pattern.range(None);
+        let span: Span = rpc_span("Scan", &caller);
+        let permitted: Result<(), Status> =
+            self.permit(&caller, &caller.ns, "Scan", || Access::Range(lower, upper));
+        span_record(&span, &permitted);
+        self.count("Scan", &permitted);
+        permitted?;
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let sending = scan_send(self.clone(), caller.ns, pattern, filter, tx);
+        tokio::spawn(sending.instrument(span));
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn del_range(
+        &self,
+        request: Request<DelRangeRequest>,
+    ) -> std::result::Result<Response<DelRangeResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: DelRangeRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::DelRange(iq, tx);
+        let res: DelRangeResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn count_range(
+        &self,
+        request: Request<CountRangeRequest>,
+    ) -> std::result::Result<Response<CountRangeResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: CountRangeRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::CountRange(iq, tx);
+        let res: CountRangeResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn exists(
+        &self,
+        request: Request<ExistsRequest>,
+    ) -> std::result::Result<Response<ExistsResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: ExistsRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Exists(iq, tx);
+        let res: ExistsResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn r#type(
+        &self,
+        request: Request<TypeRequest>,
+    ) -> std::result::Result<Response<TypeResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: TypeRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Type(iq, tx);
+        let res: TypeResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn rename(
+        &self,
+        request: Request<RenameRequest>,
+    ) -> std::result::Result<Response<RenameResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: RenameRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Rename(iq, tx);
+        let res: RenameResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn copy(
+        &self,
+        request: Request<CopyRequest>,
+    ) -> std::result::Result<Response<CopyResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: CopyRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Copy(iq, tx);
+        let res: CopyResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn s_pop(
+        &self,
+        request: Request<SPopRequest>,
+    ) -> std::result::Result<Response<SPopResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: SPopRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::SPop(iq, tx);
+        let res: SPopResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn s_rand_member(
+        &self,
+        request: Request<SRandMemberRequest>,
+    ) -> std::result::Result<Response<SRandMemberResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: SRandMemberRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::SRandMember(iq, tx);
+        let res: SRandMemberResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn random_key(
+        &self,
+        request: Request<RandomKeyRequest>,
+    ) -> std::result::Result<Response<RandomKeyResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: RandomKeyRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::RandomKey(iq, tx);
+        let res: RandomKeyResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn flush_db(
+        &self,
+        request: Request<FlushDbRequest>,
+    ) -> std::result::Result<Response<FlushDbResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: FlushDbRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::FlushDb(iq, tx);
+        let res: FlushDbResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn db_size(
+        &self,
+        request: Request<DbSizeRequest>,
+    ) -> std::result::Result<Response<DbSizeResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: DbSizeRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::DbSize(iq, tx);
+        let res: DbSizeResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn r#move(
+        &self,
+        request: Request<MoveRequest>,
+    ) -> std::result::Result<Response<MoveResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: MoveRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Move(iq, tx);
+        let res: MoveResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn acl_who_am_i(
+        &self,
+        request: Request<AclWhoAmIRequest>,
+    ) -> std::result::Result<Response<AclWhoAmIResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        Ok(Response::new(AclWhoAmIResponse {
+            principal: caller.principal.name,
+        }))
+    }
+
+    async fn acl_list(
+        &self,
+        request: Request<AclListRequest>,
+    ) -> std::result::Result<Response<AclListResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        self.permit(&caller, &caller.ns, "AclList", || Access::All)?;
+        let rules: Vec<AclRule> = self
+            .acl
+            .rules()
+            .iter()
+            .map(|r: &Rule| AclRule {
+                principal: r.principal.clone(),
+                commands: r.commands.clone(),
+                prefixes: r.prefixes.clone(),
+                namespaces: r.namespaces.clone(),
+            })
+            .collect();
+        Ok(Response::new(AclListResponse {
+            enabled: self.acl.is_enabled(),
+            rules,
+        }))
+    }
+
+    async fn slow_log_get(
+        &self,
+        request: Request<SlowLogGetRequest>,
+    ) -> std::result::Result<Response<SlowLogGetResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: SlowLogGetRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::SlowLogGet(iq, tx);
+        let res: SlowLogGetResponse = self.call(caller, req, rx).await?;
+        Ok(Response::new(res))
+    }
+
+    async fn slow_log_reset(
+        &self,
+        request: Request<SlowLogResetRequest>,
+    ) -> std::result::Result<Response<SlowLogResetResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: SlowLogResetRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::SlowLogReset(iq, tx);
+        let res: SlowLogResetResponse = self.call(caller, req, rx).await?;
         Ok(Response::new(res))
     }
+
+    async fn info(
+        &self,
+        request: Request<InfoRequest>,
+    ) -> std::result::Result<Response<InfoResponse>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        let iq: InfoRequest = request.into_inner();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        let req = Req::Info(iq, tx);
+        let stats: Stats = self.call(caller, req, rx).await?;
+        Ok(Response::new(self.info_new(stats)))
+    }
+
+    async fn execute(
+        &self,
+        request: Request<Streaming<ExecuteRequest>>,
+    ) -> std::result::Result<Response<Self::ExecuteStream>, Status> {
+        let caller: Caller = caller_get(&request)?;
+        self.count("Execute", &Ok::<_, Status>(()));
+        let span: Span = rpc_span("Execute", &caller);
+        let incoming: Streaming<ExecuteRequest> = request.into_inner();
+        let (ptx, prx) = tokio::sync::mpsc::channel(PIPELINE_DEPTH_DEFAULT);
+        let (tx, rx) = tokio::sync::mpsc::channel(PIPELINE_DEPTH_DEFAULT);
+        let reading = execute_read(incoming, self.clone(), caller, ptx);
+        tokio::spawn(reading.instrument(span));
+        tokio::spawn(execute_write(self.clone(), prx, tx));
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
 }
 
-pub async fn start(mut requests: Receiver<Req>, conf: Conf) {
-    let mut kv: BTreeMap<Vec<u8>, Val> = BTreeMap::new();
+pub async fn start(
+    mut requests: Receiver<Envelope>,
+    mut closing: watch::Receiver<bool>,
+    conf: Conf,
+) {
+    let mut dbs: Dbs = Dbs::default();
+    let mut memory: Memory = Memory::default();
+    let mut rng: StdRng = match conf.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
 
     loop {
-        let oreq: Option<Req> = requests.recv().await;
-        match oreq {
+        let deadline: Option<Instant> = dbs.next_deadline();
+        let open: bool = !dbs.is_closed();
+        let oenv: Option<Envelope> = tokio::select! {
+            oenv = requests.recv() => oenv,
+            _ = sleep_until(deadline) => {
+                dbs.expire(Instant::now());
+                continue;
+            }
+            // The waiters would keep the actor until their deadlines.
+            _ = closed(&mut closing), if open => {
+                dbs.close();
+                continue;
+            }
+        };
+        match oenv {
             None => return,
-            Some(req) => req.handle(&mut kv, &conf).await,
+            Some(env) => env.handle(&mut dbs, &mut memory, &mut rng, &conf).await,
         }
     }
 }
 
-pub async fn chan_svc_new(conf: Conf) -> impl MemoryDatabaseService {
+/// Closes the service; the actor ends after handling the queued requests.
+pub struct Closer {
+    closing: watch::Sender<bool>,
+    actor: JoinHandle<()>,
+}
+
+impl Closer {
+    /// Rejects the new requests, fails the lock waiters and ends the open execute streams.
+    pub fn close(&self) {
+        self.closing.send_replace(true);
+    }
+
+    /// Completes when closed; used to shut down the servers.
+    pub fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut closing: watch::Receiver<bool> = self.closing.subscribe();
+        async move { closed(&mut closing).await }
+    }
+
+    /// Waits the actor to drain the queued requests; every service must be dropped.
+    pub async fn drain(self) -> Result<(), Status> {
+        self.actor
+            .await
+            .map_err(|e| Status::internal(format!("the actor failed: {e}")))
+    }
+}
+
+pub async fn chan_svc_new_closable(conf: Conf) -> (ChanSvc, Closer) {
     let (tx, rx) = tokio::sync::mpsc::channel(1);
-    tokio::spawn(async move { start(rx, conf).await });
-    ChanSvc { sender: tx }
+    let (closing, closing_rx) = watch::channel(false);
+    let acl: Arc<Acl> = conf.acl.clone();
+    let metrics: Option<Arc<Metrics>> = conf.metrics.clone();
+    let clients: Arc<Clients> = conf.clients.clone();
+    let config: InfoConfig = conf.info();
+    let actor_closing: watch::Receiver<bool> = closing_rx.clone();
+    let actor: JoinHandle<()> = tokio::spawn(async move { start(rx, actor_closing, conf).await });
+    let svc = ChanSvc {
+        sender: Queue::new(tx),
+        acl,
+        closing: closing_rx,
+        metrics,
+        clients,
+        config,
+        started: Instant::now(),
+        start_time: SystemTime::now(),
+    };
+    (svc, Closer { closing, actor })
+}
+
+pub async fn chan_svc_new(conf: Conf) -> impl MemoryDatabaseService {
+    let (svc, _) = chan_svc_new_closable(conf).await;
+    svc
 }
 
 pub async fn chan_svc_new_default() -> impl MemoryDatabaseService {
     chan_svc_new(Conf::default()).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::memory::entry_size;
+
+    struct Actor {
+        dbs: Dbs,
+        memory: Memory,
+        rng: StdRng,
+        conf: Conf,
+    }
+
+    impl Actor {
+        fn new(max_memory: usize) -> Self {
+            Self {
+                dbs: Dbs::default(),
+                memory: Memory::default(),
+                rng: StdRng::seed_from_u64(42),
+                conf: Conf {
+                    max_memory,
+                    eviction: Eviction::AllKeysLru,
+                    ..Default::default()
+                },
+            }
+        }
+
+        async fn run<Q, R>(
+            &mut self,
+            ns: &str,
+            q: Q,
+            wrap: fn(Q, Sender<Result<R, Status>>) -> Req,
+        ) {
+            let (tx, _rx) = tokio::sync::mpsc::channel(1);
+            Envelope::new(ns.into(), wrap(q, tx))
+                .handle(&mut self.dbs, &mut self.memory, &mut self.rng, &self.conf)
+                .await
+        }
+
+        /// Counts the keys of the namespaces one by one.
+        fn scan(&self, namespaces: &[&str]) -> Stats {
+            let mut stats = Stats::default();
+            let kvs = namespaces.iter().filter_map(|ns| self.dbs.get(ns));
+            for (key, val) in kvs.flat_map(|db| db.kv.iter()) {
+                match val {
+                    Val::Var(_) => stats.vars += 1,
+                    Val::Map(_) => stats.maps += 1,
+                    Val::Set(_) => stats.sets += 1,
+                    Val::Deq(_) => stats.deqs += 1,
+                }
+                stats.elements += val.len() as u64;
+                stats.memory += entry_size(key, val) as u64;
+            }
+            stats
+        }
+    }
+
+    fn text(s: &str) -> Option<Value> {
+        Some(Value {
+            kind: Some(Kind::StringValue(s.into())),
+        })
+    }
+
+    fn included(key: &[u8]) -> Option<RBound> {
+        Some(RBound {
+            bound: Some(IBound::Included(key.to_vec())),
+        })
+    }
+
+    async fn workload(a: &mut Actor) {
+        for i in 0..20u8 {
+            let key: Vec<u8> = vec![b'v', i];
+            let q = SetRequest {
+                key,
+                value: text("value"),
+                ..Default::default()
+            };
+            a.run("", q, Req::Set).await;
+        }
+        let q = PushRequest {
+            key: b"q".to_vec(),
+            values: vec![text("a").unwrap(), number_new(1.0), text("bcd").unwrap()],
+            ..Default::default()
+        };
+        a.run("", q, Req::Push).await;
+        let q = PopRequest {
+            key: b"q".to_vec(),
+            ..Default::default()
+        };
+        a.run("", q, Req::Pop).await;
+        for (dkey, value) in [(b"a", "x"), (b"b", "yy"), (b"a", "zzz")] {
+            let q = DSetRequest {
+                key: b"m".to_vec(),
+                dkey: dkey.to_vec(),
+                value: text(value),
+            };
+            a.run("", q, Req::DSet).await;
+        }
+        let q = DIncrByRequest {
+            key: b"m".to_vec(),
+            dkey: b"n".to_vec(),
+            delta: 3,
+        };
+        a.run("", q, Req::DIncrBy).await;
+        for member in [&b"a"[..], b"bb", b"a", b"ccc"] {
+            let q = SAddRequest {
+                key: b"s".to_vec(),
+                val: member.to_vec(),
+            };
+            a.run("", q, Req::SAdd).await;
+        }
+        let q = SDelRequest {
+            key: b"s".to_vec(),
+            val: b"bb".to_vec(),
+        };
+        a.run("", q, Req::SDel).await;
+        let q = SPopRequest {
+            key: b"s".to_vec(),
+            count: 1,
+        };
+        a.run("", q, Req::SPop).await;
+        let q = AppendRequest {
+            key: b"v\x01".to_vec(),
+            value: "appended".into(),
+        };
+        a.run("", q, Req::Append).await;
+        let q = SetRangeRequest {
+            key: b"str".to_vec(),
+            offset: 3,
+            value: "set".into(),
+        };
+        a.run("", q, Req::SetRange).await;
+        let q = IncrRequest {
+            key: b"v\x02".to_vec(),
+        };
+        a.run("", q, Req::Incr).await;
+        let q = IncrRequest { key: b"c".to_vec() };
+        a.run("", q, Req::Incr).await;
+        let q = RenameRequest {
+            key: b"m".to_vec(),
+            new_key: b"renamed".to_vec(),
+            nx: false,
+        };
+        a.run("", q, Req::Rename).await;
+        let q = CopyRequest {
+            key: b"renamed".to_vec(),
+            new_key: b"v\x03".to_vec(),
+            replace: true,
+        };
+        a.run("", q, Req::Copy).await;
+        let q = SetRequest {
+            key: b"s".to_vec(),
+            value: text("replaces the set"),
+            ..Default::default()
+        };
+        a.run("", q, Req::Set).await;
+        let q = DelRequest {
+            keys: vec![b"v\x04".to_vec(), b"v\x04".to_vec(), b"missing".to_vec()],
+            ..Default::default()
+        };
+        a.run("", q, Req::Del).await;
+        let q = DelRangeRequest {
+            lower: included(b"v\x05"),
+            upper: included(b"v\x09"),
+        };
+        a.run("", q, Req::DelRange).await;
+        for key in [&b"q"[..], b"v\x10"] {
+            let q = MoveRequest {
+                key: key.to_vec(),
+                namespace: "other".into(),
+                replace: false,
+            };
+            a.run("", q, Req::Move).await;
+        }
+        let q = SetRequest {
+            key: b"flushed".to_vec(),
+            value: text("value"),
+            ..Default::default()
+        };
+        a.run("flushed", q, Req::Set).await;
+        a.run("flushed", FlushDbRequest {}, Req::FlushDb).await;
+        // Left as grown by the deltas.
+        let q = PushRequest {
+            key: b"q2".to_vec(),
+            values: vec![text("a").unwrap(), text("bc").unwrap()],
+            ..Default::default()
+        };
+        a.run("other", q, Req::Push).await;
+        let q = DSetRequest {
+            key: b"m2".to_vec(),
+            dkey: b"d".to_vec(),
+            value: text("value"),
+        };
+        a.run("", q, Req::DSet).await;
+        let q = SAddRequest {
+            key: b"s2".to_vec(),
+            val: b"member".to_vec(),
+        };
+        a.run("", q, Req::SAdd).await;
+    }
+
+    #[test]
+    fn members_sample_picks() {
+        let s: BTreeSet<Vec<u8>> = (0..100u8).map(|i| vec![i]).collect();
+        let mut rng = StdRng::seed_from_u64(7);
+        let distinct: Vec<Vec<u8>> = members_sample(&s, &mut rng, 150, false);
+        assert_eq!(distinct.len(), 100);
+        assert_eq!(distinct.iter().collect::<BTreeSet<_>>().len(), 100);
+        let sorted: Vec<Vec<u8>> = s.iter().cloned().collect();
+        assert_ne!(distinct, sorted);
+        let repeated: Vec<Vec<u8>> = members_sample(&s, &mut rng, 1000, true);
+        assert_eq!(repeated.len(), 1000);
+        assert!(repeated.iter().all(|m| s.contains(m)));
+        let last: Vec<Vec<u8>> = members_sample(&s, &mut rng, 1, false);
+        assert_eq!(last.len(), 1);
+        assert!(members_sample(&BTreeSet::new(), &mut rng, 3, true).is_empty());
+    }
+
+    #[tokio::test]
+    async fn stats_match_scan() {
+        for max_memory in [0, 1 << 30] {
+            let mut a = Actor::new(max_memory);
+            workload(&mut a).await;
+            let stats: Stats = a.dbs.stats(&a.memory);
+            let scanned: Stats = a.scan(&["", "other", "flushed"]);
+            assert_eq!(stats.vars, scanned.vars);
+            assert_eq!(stats.maps, scanned.maps);
+            assert_eq!(stats.sets, scanned.sets);
+            assert_eq!(stats.deqs, scanned.deqs);
+            assert_eq!(stats.elements, scanned.elements);
+            assert_eq!(stats.memory, scanned.memory);
+            assert!(0 < scanned.maps && 0 < scanned.deqs);
+        }
+    }
+
+    #[test]
+    fn substring_offsets() {
+        assert_eq!(substring("hello", 0, -1), "hello");
+        assert_eq!(substring("hello", 1, 3), "ell");
+        assert_eq!(substring("hello", -3, -2), "ll");
+        assert_eq!(substring("hello", -100, 1), "he");
+        assert_eq!(substring("hello", 3, 100), "lo");
+        assert_eq!(substring("hello", 5, 10), "");
+        assert_eq!(substring("hello", 3, 1), "");
+        assert_eq!(substring("hello", -1, -100), "");
+        assert_eq!(substring("", 0, -1), "");
+        assert_eq!(substring("hello", i64::MIN, i64::MAX), "hello");
+        // The character cut at the end is replaced.
+        assert_eq!(substring("h\u{e9}llo", 0, 1), "h\u{fffd}");
+    }
+
+    #[test]
+    fn overwrite_offsets() {
+        let mut s = String::from("hello");
+        overwrite(&mut s, 1, "EL");
+        assert_eq!(s, "hELlo");
+        overwrite(&mut s, 4, "O!!");
+        assert_eq!(s, "hELlO!!");
+        overwrite(&mut s, 9, "x");
+        assert_eq!(s, "hELlO!!\0\0x");
+        let mut empty = String::new();
+        overwrite(&mut empty, 0, "");
+        assert_eq!(empty, "");
+    }
+
+    #[test]
+    fn check_overwrite_boundaries() {
+        let s: &str = "h\u{e9}llo";
+        assert!(check_overwrite(s, 0, "ab").is_err());
+        assert!(check_overwrite(s, 2, "x").is_err());
+        assert!(check_overwrite(s, 1, "xy").is_ok());
+        assert!(check_overwrite(s, 3, "xy").is_ok());
+        assert!(check_overwrite(s, 100, "x").is_ok());
+        assert!(check_overwrite(s, usize::MAX, "x").is_ok());
+        assert!(check_overwrite("\u{e9}", 0, "").is_ok());
+    }
+}
@@ -0,0 +1,103 @@
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use tonic::transport::server::Connected;
+
+/// Counts the open connections.
+#[derive(Default)]
+pub struct Clients {
+    connected: AtomicU64,
+}
+
+impl Clients {
+    pub fn connected(&self) -> u64 {
+        self.connected.load(Ordering::Relaxed)
+    }
+}
+
+/// The connection counted until dropped.
+pub struct Counted<T> {
+    io: T,
+    clients: Arc<Clients>,
+}
+
+impl<T> Counted<T> {
+    pub fn new(io: T, clients: Arc<Clients>) -> Self {
+        clients.connected.fetch_add(1, Ordering::Relaxed);
+        Self { io, clients }
+    }
+}
+
+impl<T> Drop for Counted<T> {
+    fn drop(&mut self) {
+        self.clients.connected.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Counted<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Counted<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+/// Keeps the info of the connection(e.g. the peer certificates).
+impl<T: Connected> Connected for Counted<T> {
+    type ConnectInfo = T::ConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.io.connect_info()
+    }
+}
+
+/// Counts the connections accepted.
+pub fn counted<S, T, E>(
+    incoming: S,
+    clients: Arc<Clients>,
+) -> impl Stream<Item = Result<Counted<T>, E>>
+where
+    S: Stream<Item = Result<T, E>>,
+{
+    incoming.map(move |r: Result<T, E>| r.map(|io: T| Counted::new(io, clients.clone())))
+}
@@ -1,9 +1,29 @@
+#![allow(clippy::result_large_err)]
+
 pub mod memdatabase {
     pub mod v1 {
         tonic::include_proto!("memdatabase.v1");
+
+        /// The descriptors for the reflection service.
+        pub const FILE_DESCRIPTOR_SET: &[u8] =
+            tonic::include_file_descriptor_set!("memdatabase_descriptor");
     }
 }
 
 pub mod value;
 
+pub mod acl;
+pub mod auth;
+pub mod clients;
+pub mod listen;
+pub mod tls;
+pub mod trace;
+
+pub mod lock;
+pub mod memory;
+pub mod metrics;
+pub mod slowlog;
+
+pub mod pattern;
+
 pub mod chan;
@@ -0,0 +1,83 @@
+use core::net::SocketAddr;
+use core::str::FromStr;
+
+use std::fs::Permissions;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use tokio::net::UnixListener;
+
+use tonic::Status;
+
+pub const UNIX_PREFIX: &str = "unix:";
+
+/// The address to listen on; TLS applies to the TCP addresses only.
+pub enum Listen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for Listen {
+    type Err = Status;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(UNIX_PREFIX) {
+            Some("") => Err(Status::invalid_argument("empty socket path")),
+            Some(path) => Ok(Self::Unix(path.into())),
+            None => str::parse(s)
+                .map(Self::Tcp)
+                .map_err(|e| Status::invalid_argument(format!("invalid listen addr: {e}"))),
+        }
+    }
+}
+
+/// Parses the addresses separated by commas.
+pub fn listens_parse(s: &str) -> Result<Vec<Listen>, Status> {
+    s.split(',').map(str::trim).map(str::parse).collect()
+}
+
+/// Parses the permissions of the socket file in octal(e.g. 660).
+pub fn mode_parse(s: &str) -> Result<u32, Status> {
+    u32::from_str_radix(s, 8).map_err(|e| Status::invalid_argument(format!("invalid mode: {e}")))
+}
+
+/// Binds the socket; removes the stale socket file left by the previous process.
+pub fn unix_bind(path: &Path, mode: Option<u32>) -> Result<UnixListener, Status> {
+    let stale: bool = std::fs::symlink_metadata(path)
+        .map(|m| m.file_type().is_socket())
+        .unwrap_or(false);
+    if stale {
+        std::fs::remove_file(path)
+            .map_err(|e| Status::internal(format!("unable to remove the socket: {e}")))?;
+    }
+    let listener: UnixListener =
+        UnixListener::bind(path).map_err(|e| Status::internal(format!("unable to listen: {e}")))?;
+    if let Some(mode) = mode {
+        std::fs::set_permissions(path, Permissions::from_mode(mode))
+            .map_err(|e| Status::internal(format!("unable to set the mode: {e}")))?;
+    }
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_parse_octal() {
+        assert_eq!(mode_parse("660").unwrap(), 0o660);
+        assert_eq!(mode_parse("0600").unwrap(), 0o600);
+        assert!(mode_parse("680").is_err());
+        assert!(mode_parse("rw").is_err());
+        assert!(mode_parse("").is_err());
+    }
+
+    #[test]
+    fn listens_parse_mixed() {
+        let listens: Vec<Listen> = listens_parse("127.0.0.1:50051, unix:/tmp/m.sock").unwrap();
+        assert!(matches!(&listens[0], Listen::Tcp(a) if a.port() == 50051));
+        assert!(matches!(&listens[1], Listen::Unix(p) if p == Path::new("/tmp/m.sock")));
+        assert!(listens_parse("unix:").is_err());
+        assert!(listens_parse("localhost").is_err());
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant, SystemTime};
+
+use tracing::warn;
+
+use tokio::sync::mpsc::Sender;
+
+use tonic::Status;
+
+use crate::memdatabase::v1::AcquireResponse;
+
+pub struct Lease {
+    pub owner: String,
+    pub token: u64,
+    pub expires: Instant,
+}
+
+impl Lease {
+    pub fn held_by(&self, owner: &str, token: u64) -> bool {
+        self.owner == owner && self.token == token
+    }
+}
+
+/// The contender blocked until the lock gets free or the deadline.
+pub struct Waiter {
+    pub owner: String,
+    pub ttl: Duration,
+    pub deadline: Instant,
+    pub reply: Sender<Result<AcquireResponse, Status>>,
+}
+
+#[derive(Default)]
+pub struct Lock {
+    pub lease: Option<Lease>,
+    pub waiters: VecDeque<Waiter>,
+}
+
+#[derive(Default)]
+pub struct Locks {
+    locks: BTreeMap<Vec<u8>, Lock>,
+    last_token: u64,
+    /// The number of the leases expired.
+    expired: u64,
+    /// Fails the contenders instead of queuing them while shutting down.
+    closed: bool,
+}
+
+pub fn instant2time(i: Instant, now: Instant) -> SystemTime {
+    SystemTime::now() + i.saturating_duration_since(now)
+}
+
+pub fn reply_waiter(w: Waiter, res: AcquireResponse) {
+    match w.reply.try_send(Ok(res)) {
+        Ok(_) => {}
+        Err(e) => warn!("the waiter gone: {e}"),
+    }
+}
+
+/// Fails the waiter not to keep the shutdown waiting.
+pub fn fail_waiter(w: Waiter) {
+    match w.reply.try_send(Err(Status::unavailable("shutting down"))) {
+        Ok(_) => {}
+        Err(e) => warn!("the waiter gone: {e}"),
+    }
+}
+
+impl Lock {
+    /// Drops the expired lease and the waiters which gave up, then grants the lease to the next waiter.
+    ///
+    /// Returns true if the lease expired.
+    pub fn refresh(&mut self, now: Instant, last_token: &mut u64) -> bool {
+        let expired: bool = self
+            .lease
+            .as_ref()
+            .map(|l| l.expires <= now)
+            .unwrap_or(false);
+        if expired {
+            self.lease = None;
+        }
+
+        let (waiting, gone): (VecDeque<Waiter>, VecDeque<Waiter>) = self
+            .waiters
+            .drain(..)
+            .partition(|w| now < w.deadline && !w.reply.is_closed());
+        self.waiters = waiting;
+        for w in gone {
+            reply_waiter(w, AcquireResponse::default());
+        }
+
+        while self.lease.is_none() {
+            let Some(w) = self.waiters.pop_front() else {
+                return expired;
+            };
+            self.grant(w, now, last_token);
+        }
+        expired
+    }
+
+    /// Gives the lease with the new fencing token to the waiter if it still waits.
+    pub fn grant(&mut self, w: Waiter, now: Instant, last_token: &mut u64) {
+        *last_token += 1;
+        let lease = Lease {
+            owner: w.owner,
+            token: *last_token,
+            expires: now + w.ttl,
+        };
+        let res = AcquireResponse {
+            acquired: true,
+            token: lease.token,
+            expire_time: Some(instant2time(lease.expires, now).into()),
+        };
+        match w.reply.try_send(Ok(res)) {
+            Ok(_) => self.lease = Some(lease),
+            Err(e) => warn!("the waiter gone: {e}"),
+        }
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let lease: Option<Instant> = self.lease.as_ref().map(|l| l.expires);
+        let waiters = self.waiters.iter().map(|w| w.deadline);
+        lease.into_iter().chain(waiters).min()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lease.is_none() && self.waiters.is_empty()
+    }
+}
+
+impl Locks {
+    /// Continues the fencing tokens issued before the locks were dropped.
+    pub fn resumed(last_token: u64) -> Self {
+        Self {
+            last_token,
+            ..Default::default()
+        }
+    }
+
+    pub fn last_token(&self) -> u64 {
+        self.last_token
+    }
+
+    fn refresh(&mut self, name: &[u8], now: Instant) -> Option<&mut Lock> {
+        let lock: &mut Lock = self.locks.get_mut(name)?;
+        if lock.refresh(now, &mut self.last_token) {
+            self.expired += 1;
+        }
+        Some(lock)
+    }
+
+    fn cleanup(&mut self, name: &[u8]) {
+        let empty: bool = self.locks.get(name).map(Lock::is_empty).unwrap_or(false);
+        if empty {
+            self.locks.remove(name);
+        }
+    }
+
+    /// Grants the lease or queues the waiter; the reply is sent when the result is known.
+    pub fn acquire(&mut self, name: Vec<u8>, waiter: Waiter, now: Instant) {
+        self.refresh(&name, now);
+        let closed: bool = self.closed;
+        let lock: &mut Lock = self.locks.entry(name.clone()).or_default();
+        let queue: bool = lock.lease.is_some() || !lock.waiters.is_empty();
+        match (queue, now < waiter.deadline, closed) {
+            (true, true, false) => lock.waiters.push_back(waiter),
+            (true, true, true) => fail_waiter(waiter),
+            (true, false, _) => reply_waiter(waiter, AcquireResponse::default()),
+            (false, _, _) => lock.grant(waiter, now, &mut self.last_token),
+        }
+        self.cleanup(&name);
+    }
+
+    /// Extends the lease held by the owner.
+    pub fn renew(
+        &mut self,
+        name: &[u8],
+        owner: &str,
+        token: u64,
+        expires: Instant,
+        now: Instant,
+    ) -> Result<Instant, Status> {
+        let olease: Option<&mut Lease> = self.refresh(name, now).and_then(|l| l.lease.as_mut());
+        let lease: &mut Lease = olease
+            .filter(|l| l.held_by(owner, token))
+            .ok_or_else(|| Status::failed_precondition("the lease is not held"))?;
+        lease.expires = expires;
+        Ok(expires)
+    }
+
+    /// Releases the lease held by the owner and passes the lock to the next waiter.
+    pub fn release(&mut self, name: &[u8], owner: &str, token: u64, now: Instant) -> bool {
+        let released: bool = match self.locks.get_mut(name) {
+            None => false,
+            Some(lock) => {
+                if lock.refresh(now, &mut self.last_token) {
+                    self.expired += 1;
+                }
+                let held: bool = lock
+                    .lease
+                    .as_ref()
+                    .map(|l| l.held_by(owner, token))
+                    .unwrap_or(false);
+                if held {
+                    lock.lease = None;
+                    let _: bool = lock.refresh(now, &mut self.last_token);
+                }
+                held
+            }
+        };
+        self.cleanup(name);
+        released
+    }
+
+    /// Expires the leases and the waiters passed their deadlines.
+    pub fn expire(&mut self, now: Instant) {
+        let last_token: &mut u64 = &mut self.last_token;
+        let expired: &mut u64 = &mut self.expired;
+        self.locks.retain(|_, lock| {
+            if lock.refresh(now, last_token) {
+                *expired += 1;
+            }
+            !lock.is_empty()
+        });
+    }
+
+    /// Fails the waiters; the leases are kept until released or expired.
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.locks.retain(|_, lock| {
+            for w in lock.waiters.drain(..) {
+                fail_waiter(w);
+            }
+            !lock.is_empty()
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locks.is_empty()
+    }
+
+    pub fn expired(&self) -> u64 {
+        self.expired
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.locks.values().filter_map(Lock::next_deadline).min()
+    }
+}
+
+/// Sleeps until the deadline; never wakes up if no deadline.
+pub async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(d) => tokio::time::sleep_until(d.into()).await,
+        None => futures::future::pending().await,
+    }
+}
+
+pub fn duration_convert(od: Option<prost_types::Duration>) -> Result<Duration, Status> {
+    let d: prost_types::Duration = od.ok_or_else(|| Status::invalid_argument("no duration"))?;
+    Duration::try_from(d).map_err(|e| Status::invalid_argument(format!("invalid duration: {e}")))
+}
+
+/// Gets the instant after the duration; rejects the duration too long to be represented.
+pub fn deadline_new(now: Instant, d: Duration) -> Result<Instant, Status> {
+    now.checked_add(d)
+        .ok_or_else(|| Status::invalid_argument("duration too long"))
+}
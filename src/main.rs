@@ -1,43 +1,332 @@
+#![allow(clippy::result_large_err)]
+
+use core::future::Future;
 use core::net::SocketAddr;
+use core::pin::Pin;
 
 use std::env;
+use std::path::PathBuf;
 use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::TryFutureExt;
+
+use tracing::{error, info};
 
-use log::error;
+use tokio::net::{TcpListener, UnixListener};
+use tokio::signal::unix::{signal, SignalKind};
 
-use tonic::transport::{server::Router, Server};
+use tokio_stream::wrappers::UnixListenerStream;
+
+use tonic::server::NamedService;
+use tonic::transport::server::{Router, TcpIncoming};
+use tonic::transport::Server;
 use tonic::Status;
 
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+
 use memdatabase::memdatabase::v1::memory_database_service_server::MemoryDatabaseServiceServer;
+use memdatabase::memdatabase::v1::FILE_DESCRIPTOR_SET;
 
-use memdatabase::chan::btree::svc::chan_svc_new_default;
+use memdatabase::chan::btree::svc::{chan_svc_new_closable, ChanSvc, Conf};
+use memdatabase::memory::Eviction;
+use memdatabase::metrics::{metrics_serve, Metrics};
+use memdatabase::slowlog::{SLOWLOG_MAX_LEN_DEFAULT, SLOWLOG_THRESHOLD_DEFAULT};
+
+use memdatabase::acl::{rules_parse, Acl, Rule};
+use memdatabase::auth::{credentials_parse, Auth, Credential};
+use memdatabase::clients::{counted, Clients};
+use memdatabase::listen::{listens_parse, mode_parse, unix_bind, Listen};
+use memdatabase::tls::{incoming, Reloader, TlsFiles, RELOAD_INTERVAL_DEFAULT};
+use memdatabase::trace::{tracing_init, tracing_shutdown};
 
 const LISTEN_ADDR_DEFAULT: &str = "0.0.0.0:50051";
 
+/// How long to wait the in-flight requests on shutdown.
+const SHUTDOWN_TIMEOUT_DEFAULT: Duration = Duration::from_secs(30);
+
+type Serving = Pin<Box<dyn Future<Output = Result<(), Status>> + Send>>;
+
+fn serve_err(e: tonic::transport::Error) -> Status {
+    Status::internal(format!("unable to serve: {e}"))
+}
+
+/// Gets the address of the metrics server; off if ENV_METRICS_ADDR is not set.
+fn metrics_addr() -> Result<Option<SocketAddr>, Status> {
+    match env::var("ENV_METRICS_ADDR") {
+        Err(_) => Ok(None),
+        Ok(s) => str::parse(s.as_str())
+            .map(Some)
+            .map_err(|e| Status::invalid_argument(format!("invalid metrics addr: {e}"))),
+    }
+}
+
+fn conf_new() -> Result<Conf, Status> {
+    let seed: Option<u64> = match env::var("ENV_RANDOM_SEED").ok() {
+        None => None,
+        Some(s) => Some(
+            str::parse(s.as_str())
+                .map_err(|e| Status::invalid_argument(format!("invalid random seed: {e}")))?,
+        ),
+    };
+    let max_memory: usize = match env::var("ENV_MAX_MEMORY").ok() {
+        None => 0,
+        Some(s) => str::parse(s.as_str())
+            .map_err(|e| Status::invalid_argument(format!("invalid max memory: {e}")))?,
+    };
+    let eviction: Eviction = match env::var("ENV_EVICTION_POLICY").ok() {
+        None => Eviction::default(),
+        Some(s) => str::parse(s.as_str())?,
+    };
+    let rules: Vec<Rule> = match env::var("ENV_ACL_FILE") {
+        Err(_) => vec![],
+        Ok(path) => {
+            let s: String = std::fs::read_to_string(path)
+                .map_err(|e| Status::invalid_argument(format!("unable to read the acl: {e}")))?;
+            rules_parse(s.as_str())?
+        }
+    };
+    let metrics: Option<Arc<Metrics>> = match metrics_addr()? {
+        None => None,
+        Some(_) => Some(Arc::new(Metrics::new()?)),
+    };
+    let slowlog_threshold: Duration = match env::var("ENV_SLOWLOG_THRESHOLD_MICROS").ok() {
+        None => SLOWLOG_THRESHOLD_DEFAULT,
+        Some(s) => str::parse(s.as_str())
+            .map(Duration::from_micros)
+            .map_err(|e| Status::invalid_argument(format!("invalid slowlog threshold: {e}")))?,
+    };
+    let slowlog_max_len: usize = match env::var("ENV_SLOWLOG_MAX_LEN").ok() {
+        None => SLOWLOG_MAX_LEN_DEFAULT,
+        Some(s) => str::parse(s.as_str())
+            .map_err(|e| Status::invalid_argument(format!("invalid slowlog max len: {e}")))?,
+    };
+    Ok(Conf {
+        seed,
+        max_memory,
+        eviction,
+        acl: Arc::new(Acl::new(rules)),
+        metrics,
+        slowlog_threshold,
+        slowlog_max_len,
+        ..Default::default()
+    })
+}
+
+fn auth_new() -> Result<Auth, Status> {
+    let mut creds: Vec<Credential> = match env::var("ENV_AUTH_TOKENS").ok() {
+        None => vec![],
+        Some(s) => credentials_parse(s.as_str(), ',')?,
+    };
+    if let Ok(path) = env::var("ENV_AUTH_TOKENS_FILE") {
+        let s: String = std::fs::read_to_string(path)
+            .map_err(|e| Status::invalid_argument(format!("unable to read the tokens: {e}")))?;
+        creds.extend(credentials_parse(s.as_str(), '\n')?);
+    }
+    Ok(Auth::new(creds))
+}
+
+/// Gets the certificate files if ENV_TLS_CERT and ENV_TLS_KEY are set.
+fn tls_files_new() -> Result<Option<TlsFiles>, Status> {
+    let cert: Option<String> = env::var("ENV_TLS_CERT").ok();
+    let key: Option<String> = env::var("ENV_TLS_KEY").ok();
+    let client_ca: Option<PathBuf> = env::var("ENV_TLS_CLIENT_CA").ok().map(PathBuf::from);
+    match (cert, key) {
+        (None, None) => Ok(None),
+        (Some(cert), Some(key)) => Ok(Some(TlsFiles {
+            cert: cert.into(),
+            key: key.into(),
+            client_ca,
+        })),
+        _ => Err(Status::invalid_argument(
+            "both ENV_TLS_CERT and ENV_TLS_KEY required",
+        )),
+    }
+}
+
+fn tls_reload_interval() -> Result<Duration, Status> {
+    match env::var("ENV_TLS_RELOAD_SECONDS") {
+        Err(_) => Ok(RELOAD_INTERVAL_DEFAULT),
+        Ok(s) => str::parse(s.as_str())
+            .map(Duration::from_secs)
+            .map_err(|e| Status::invalid_argument(format!("invalid reload interval: {e}"))),
+    }
+}
+
+fn shutdown_timeout() -> Result<Duration, Status> {
+    match env::var("ENV_SHUTDOWN_TIMEOUT_SECONDS") {
+        Err(_) => Ok(SHUTDOWN_TIMEOUT_DEFAULT),
+        Ok(s) => str::parse(s.as_str())
+            .map(Duration::from_secs)
+            .map_err(|e| Status::invalid_argument(format!("invalid shutdown timeout: {e}"))),
+    }
+}
+
+/// Waits SIGINT or SIGTERM.
+async fn shutdown_signal() -> Result<(), Status> {
+    let mut term = signal(SignalKind::terminate())
+        .map_err(|e| Status::internal(format!("unable to handle the signal: {e}")))?;
+    let interrupted: Result<(), std::io::Error> = tokio::select! {
+        r = tokio::signal::ctrl_c() => r,
+        _ = term.recv() => Ok(()),
+    };
+    interrupted.map_err(|e| Status::internal(format!("unable to handle the signal: {e}")))
+}
+
+/// Sets the status of the service and the server as a whole.
+async fn health_set<S: NamedService>(reporter: &mut HealthReporter, _: &S, status: ServingStatus) {
+    reporter.set_service_status(S::NAME, status).await;
+    reporter.set_service_status("", status).await;
+}
+
+/// Forgets the statuses to end the open watches; they keep the connections open otherwise.
+async fn health_clear<S: NamedService>(reporter: &mut HealthReporter, _: &S) {
+    reporter.clear_service_status(S::NAME).await;
+    reporter.clear_service_status("").await;
+}
+
 async fn sub() -> Result<(), Status> {
-    let mem_svc = chan_svc_new_default().await;
-    let mem_svr: MemoryDatabaseServiceServer<_> = MemoryDatabaseServiceServer::new(mem_svc);
+    let conf: Conf = conf_new()?;
+    let auth: Auth = auth_new()?;
+    let timeout: Duration = shutdown_timeout()?;
+    let metrics: Option<Arc<Metrics>> = conf.metrics.clone();
+    let clients: Arc<Clients> = conf.clients.clone();
+    let (mem_svc, closer) = chan_svc_new_closable(conf).await;
+    let stats_svc: ChanSvc = mem_svc.clone();
+    let mem_svr = MemoryDatabaseServiceServer::with_interceptor(mem_svc, auth);
 
-    let mut server: Server = Server::builder();
-    let router: Router<_> = server.add_service(mem_svr);
+    // The health and the reflection are served without the authentication.
+    let (mut reporter, health_svr) = tonic_health::server::health_reporter();
+    health_set(&mut reporter, &mem_svr, ServingStatus::NotServing).await;
+    let reflection_svr = tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(FILE_DESCRIPTOR_SET)
+        .build()
+        .map_err(|e| Status::internal(format!("unable to build the reflection: {e}")))?;
 
     let listen_addr: String = env::var("ENV_LISTEN_ADDR")
         .ok()
         .unwrap_or_else(|| LISTEN_ADDR_DEFAULT.into());
-    let sa: SocketAddr = str::parse(listen_addr.as_str())
-        .map_err(|e| Status::invalid_argument(format!("invalid listen addr: {e}")))?;
+    let listens: Vec<Listen> = listens_parse(listen_addr.as_str())?;
+    let mode: Option<u32> = match env::var("ENV_SOCKET_MODE") {
+        Err(_) => None,
+        Ok(s) => Some(mode_parse(s.as_str())?),
+    };
+
+    let tls: Option<Reloader> = match tls_files_new()? {
+        None => None,
+        Some(files) => {
+            let reloader: Reloader = Reloader::new(files)?;
+            tokio::spawn(reloader.clone().watch(tls_reload_interval()?));
+            Some(reloader)
+        }
+    };
+
+    let mut servers: Vec<Serving> = vec![];
+    for listen in listens {
+        let mut server: Server = Server::builder();
+        let router: Router<_> = server
+            .add_service(health_svr.clone())
+            .add_service(reflection_svr.clone())
+            .add_service(mem_svr.clone());
+        let serving: Serving = match (listen, &tls) {
+            (Listen::Tcp(sa), None) => {
+                let tcp: TcpIncoming = TcpIncoming::new(sa, false, None)
+                    .map_err(|e| Status::internal(format!("unable to listen: {e}")))?;
+                Box::pin(
+                    router
+                        .serve_with_incoming_shutdown(
+                            counted(tcp, clients.clone()),
+                            closer.closed(),
+                        )
+                        .map_err(serve_err),
+                )
+            }
+            (Listen::Tcp(sa), Some(reloader)) => {
+                let listener: TcpListener = TcpListener::bind(sa)
+                    .await
+                    .map_err(|e| Status::internal(format!("unable to listen: {e}")))?;
+                Box::pin(
+                    router
+                        .serve_with_incoming_shutdown(
+                            counted(incoming(listener, reloader.clone()), clients.clone()),
+                            closer.closed(),
+                        )
+                        .map_err(serve_err),
+                )
+            }
+            (Listen::Unix(path), _) => {
+                let listener: UnixListener = unix_bind(&path, mode)?;
+                Box::pin(
+                    router
+                        .serve_with_incoming_shutdown(
+                            counted(UnixListenerStream::new(listener), clients.clone()),
+                            closer.closed(),
+                        )
+                        .map_err(serve_err),
+                )
+            }
+        };
+        servers.push(serving);
+    }
+
+    match (metrics_addr()?, metrics) {
+        (Some(sa), Some(metrics)) => servers.push(Box::pin(metrics_serve(
+            sa,
+            metrics,
+            stats_svc,
+            closer.closed(),
+        ))),
+        _ => drop(stats_svc),
+    }
 
-    router
-        .serve(sa)
-        .await
-        .map_err(|e| Status::internal(format!("unable to listen: {e}")))?;
+    // No snapshot to load; ready once the listeners are bound.
+    health_set(&mut reporter, &mem_svr, ServingStatus::Serving).await;
+
+    let mut serving = futures::future::try_join_all(servers);
+    tokio::select! {
+        served = &mut serving => {
+            served?;
+            return Ok(());
+        }
+        signaled = shutdown_signal() => signaled?,
+    }
+
+    info!("shutting down");
+    health_set(&mut reporter, &mem_svr, ServingStatus::NotServing).await;
+    health_clear(&mut reporter, &mem_svr).await;
+    closer.close();
+    // The actor ends after every service dropped.
+    drop(mem_svr);
+    tokio::time::timeout(timeout, async {
+        serving.await?;
+        closer.drain().await
+    })
+    .await
+    .map_err(|_| Status::deadline_exceeded("unable to drain the requests in time"))??;
+    // No persistence to flush.
+    info!("drained");
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> ExitCode {
-    sub().await.map(|_| ExitCode::SUCCESS).unwrap_or_else(|e| {
+    let otlp_endpoint: Option<String> = env::var("ENV_OTLP_ENDPOINT").ok();
+    match tracing_init(otlp_endpoint) {
+        Ok(_) => {}
+        Err(e) => {
+            eprintln!("{}", e.message());
+            return ExitCode::FAILURE;
+        }
+    }
+    let code: ExitCode = sub().await.map(|_| ExitCode::SUCCESS).unwrap_or_else(|e| {
         error!("{e}");
         ExitCode::FAILURE
-    })
+    });
+    match tokio::task::spawn_blocking(tracing_shutdown).await {
+        Ok(_) => {}
+        Err(e) => eprintln!("unable to export the spans: {e}"),
+    }
+    code
 }
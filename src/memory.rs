@@ -0,0 +1,449 @@
+use core::str::FromStr;
+
+use std::collections::{BTreeMap, HashMap};
+
+use prost::Message;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use tonic::Status;
+
+use prost_types::Value;
+
+use crate::memdatabase::v1::ValType;
+use crate::value::btree::Val;
+
+/// The bytes added for each key(the tree node and the allocation).
+pub const KEY_OVERHEAD: usize = 64;
+
+/// The bytes added for each item of a collection.
+pub const ITEM_OVERHEAD: usize = 16;
+
+pub const EVICTION_SAMPLES_DEFAULT: usize = 5;
+
+/// Estimates the bytes used by the entry of a map.
+pub fn dentry_size(dkey: &[u8], v: &Value) -> usize {
+    dkey.len() + v.encoded_len() + ITEM_OVERHEAD
+}
+
+/// Estimates the bytes used by the member of a set.
+pub fn member_size(m: &[u8]) -> usize {
+    m.len() + ITEM_OVERHEAD
+}
+
+/// Estimates the bytes used by the item of a queue.
+pub fn item_size(v: &Value) -> usize {
+    v.encoded_len() + ITEM_OVERHEAD
+}
+
+/// Estimates the bytes used by the value.
+pub fn val_size(v: &Val) -> usize {
+    match v {
+        Val::Var(v) => v.encoded_len(),
+        Val::Map(m) => m.iter().map(|(k, v)| dentry_size(k, v)).sum(),
+        Val::Set(s) => s.iter().map(|m| member_size(m)).sum(),
+        Val::Deq(q) => q.iter().map(item_size).sum(),
+    }
+}
+
+/// Estimates the bytes used by the key and its value.
+pub fn entry_size(key: &[u8], v: &Val) -> usize {
+    KEY_OVERHEAD + key.len() + val_size(v)
+}
+
+/// How to make room when the memory used exceeds the limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Eviction {
+    /// Rejects the writes which may use more memory.
+    #[default]
+    NoEviction,
+    /// Evicts the least recently used key among the samples.
+    AllKeysLru,
+    /// Evicts the least frequently used key among the samples.
+    AllKeysLfu,
+}
+
+impl FromStr for Eviction {
+    type Err = Status;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "noeviction" => Ok(Self::NoEviction),
+            "allkeys-lru" => Ok(Self::AllKeysLru),
+            "allkeys-lfu" => Ok(Self::AllKeysLfu),
+            // Rejected until the keys have the ttl; nothing could be evicted.
+            "volatile-ttl" => Err(Status::invalid_argument("volatile-ttl needs keys with ttl")),
+            _ => Err(Status::invalid_argument(format!("unknown policy: {s}"))),
+        }
+    }
+}
+
+impl Eviction {
+    /// Gets the name parsed by from_str.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NoEviction => "noeviction",
+            Self::AllKeysLru => "allkeys-lru",
+            Self::AllKeysLfu => "allkeys-lfu",
+        }
+    }
+}
+
+/// The namespace and the key.
+pub type Slot = (String, Vec<u8>);
+
+/// The bytes, the type and the elements of a key.
+#[derive(Clone, Copy)]
+pub struct Usage {
+    pub size: usize,
+    pub val_type: ValType,
+    /// The elements of the collection; zero for the var.
+    pub len: usize,
+}
+
+impl Usage {
+    /// Measures the key and its value.
+    pub fn new(key: &[u8], v: &Val) -> Self {
+        Self {
+            size: entry_size(key, v),
+            val_type: v.val_type(),
+            len: v.len(),
+        }
+    }
+}
+
+/// The sums over the keys tracked.
+#[derive(Default)]
+struct Totals {
+    used: usize,
+    elements: usize,
+    keys: HashMap<ValType, usize>,
+}
+
+impl Totals {
+    fn add(&mut self, u: &Usage) {
+        self.used += u.size;
+        self.elements += u.len;
+        *self.keys.entry(u.val_type).or_default() += 1;
+    }
+
+    fn sub(&mut self, u: &Usage) {
+        self.used -= u.size;
+        self.elements -= u.len;
+        if let Some(n) = self.keys.get_mut(&u.val_type) {
+            *n -= 1;
+        }
+    }
+}
+
+struct Meta {
+    usage: Usage,
+    /// The clock of the last access.
+    access: u64,
+    hits: u64,
+    /// The position in the keys of the space.
+    index: usize,
+}
+
+/// The keys of a namespace tracked.
+#[derive(Default)]
+struct Space {
+    metas: HashMap<Vec<u8>, Meta>,
+    /// The keys to sample from.
+    keys: Vec<Vec<u8>>,
+    totals: Totals,
+}
+
+impl Space {
+    fn remove(&mut self, key: &[u8]) {
+        let Some(m) = self.metas.remove(key) else {
+            return;
+        };
+        self.totals.sub(&m.usage);
+        self.keys.swap_remove(m.index);
+        if let Some(moved) = self.keys.get(m.index) {
+            if let Some(mm) = self.metas.get_mut(moved) {
+                mm.index = m.index;
+            }
+        }
+    }
+}
+
+/// Tracks the approximate bytes used by the keys to pick the keys to evict; also counts the keys.
+///
+/// Every write pays a lookup here, which also keeps the statistics and RandomKey cheap.
+#[derive(Default)]
+pub struct Memory {
+    /// Ordered to keep the eviction reproducible with the seeded random number generator.
+    spaces: BTreeMap<String, Space>,
+    clock: u64,
+}
+
+impl Memory {
+    fn sum<F>(&self, f: F) -> usize
+    where
+        F: Fn(&Totals) -> usize,
+    {
+        self.spaces.values().map(|s| f(&s.totals)).sum()
+    }
+
+    pub fn used(&self) -> usize {
+        self.sum(|t| t.used)
+    }
+
+    /// Gets the elements of all collections.
+    pub fn elements(&self) -> usize {
+        self.sum(|t| t.elements)
+    }
+
+    /// Gets the number of the keys of the type.
+    pub fn keys(&self, t: ValType) -> usize {
+        self.sum(|totals| totals.keys.get(&t).copied().unwrap_or(0))
+    }
+
+    /// Records the access to the key if tracked.
+    pub fn touch(&mut self, ns: &str, key: &[u8]) {
+        self.clock += 1;
+        let om: Option<&mut Meta> = self.spaces.get_mut(ns).and_then(|s| s.metas.get_mut(key));
+        if let Some(m) = om {
+            m.access = self.clock;
+            m.hits = m.hits.saturating_add(1);
+        }
+    }
+
+    /// Records the access and the new usage of the key.
+    pub fn resize(&mut self, ns: &str, key: &[u8], usage: Usage) {
+        self.clock += 1;
+        if !self.spaces.contains_key(ns) {
+            self.spaces.insert(ns.into(), Space::default());
+        }
+        let Some(space) = self.spaces.get_mut(ns) else {
+            return;
+        };
+        space.totals.add(&usage);
+        match space.metas.get_mut(key) {
+            Some(m) => {
+                space.totals.sub(&m.usage);
+                m.usage = usage;
+                m.access = self.clock;
+                m.hits = m.hits.saturating_add(1);
+            }
+            None => {
+                let m = Meta {
+                    usage,
+                    access: self.clock,
+                    hits: 1,
+                    index: space.keys.len(),
+                };
+                space.keys.push(key.to_vec());
+                space.metas.insert(key.to_vec(), m);
+            }
+        }
+    }
+
+    /// Records the access and the bytes added to the key; a new key starts from its overhead.
+    pub fn grow(&mut self, ns: &str, key: &[u8], v: &Val, delta: isize) {
+        let om: Option<&Meta> = self.spaces.get(ns).and_then(|s| s.metas.get(key));
+        let base: usize = match om {
+            Some(m) => m.usage.size,
+            None => KEY_OVERHEAD + key.len(),
+        };
+        let usage = Usage {
+            size: base.saturating_add_signed(delta),
+            val_type: v.val_type(),
+            len: v.len(),
+        };
+        self.resize(ns, key, usage)
+    }
+
+    pub fn remove(&mut self, ns: &str, key: &[u8]) {
+        let Some(space) = self.spaces.get_mut(ns) else {
+            return;
+        };
+        space.remove(key);
+        if space.keys.is_empty() {
+            self.spaces.remove(ns);
+        }
+    }
+
+    /// Forgets all keys of the namespace; no key is copied.
+    pub fn remove_ns(&mut self, ns: &str) {
+        self.spaces.remove(ns);
+    }
+
+    /// Picks a key of the namespace at random.
+    pub fn random_key(&self, ns: &str, rng: &mut StdRng) -> Option<&[u8]> {
+        let space: &Space = self.spaces.get(ns)?;
+        match space.keys.is_empty() {
+            true => None,
+            false => Some(&space.keys[rng.gen_range(0..space.keys.len())]),
+        }
+    }
+
+    /// Picks a key of any namespace at random; each key is equally likely.
+    fn sample(&self, rng: &mut StdRng) -> Option<(&str, &[u8], &Meta)> {
+        let total: usize = self.spaces.values().map(|s| s.keys.len()).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut i: usize = rng.gen_range(0..total);
+        for (ns, space) in &self.spaces {
+            match space.keys.get(i) {
+                Some(key) => return space.metas.get(key).map(|m| (ns.as_str(), &key[..], m)),
+                None => i -= space.keys.len(),
+            }
+        }
+        None
+    }
+
+    /// Picks the key to evict among the sampled keys; none if the policy never evicts.
+    pub fn victim(&self, policy: Eviction, samples: usize, rng: &mut StdRng) -> Option<Slot> {
+        let rank = |sampled: &(&str, &[u8], &Meta)| -> (u64, u64) {
+            let m: &Meta = sampled.2;
+            match policy {
+                Eviction::AllKeysLfu => (m.hits, m.access),
+                _ => (m.access, 0),
+            }
+        };
+        match policy {
+            Eviction::NoEviction => None,
+            Eviction::AllKeysLru | Eviction::AllKeysLfu => (0..samples.max(1))
+                .filter_map(|_| self.sample(rng))
+                .min_by_key(rank)
+                .map(|(ns, key, _)| (ns.into(), key.to_vec())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use rand::SeedableRng;
+
+    use prost_types::value::Kind;
+
+    fn var() -> Val {
+        Val::Var(Value {
+            kind: Some(Kind::NumberValue(1.0)),
+        })
+    }
+
+    fn set(members: &[&[u8]]) -> Val {
+        Val::Set(members.iter().map(|m| m.to_vec()).collect::<BTreeSet<_>>())
+    }
+
+    /// Checks every tracked key sits at its index.
+    fn check_indices(memory: &Memory) {
+        for space in memory.spaces.values() {
+            assert_eq!(space.keys.len(), space.metas.len());
+            for (i, key) in space.keys.iter().enumerate() {
+                assert_eq!(space.metas[key].index, i);
+            }
+        }
+    }
+
+    #[test]
+    fn totals_follow_the_writes() {
+        let mut memory = Memory::default();
+        let v: Val = var();
+        let s: Val = set(&[b"a", b"bb"]);
+        memory.resize("", b"k", Usage::new(b"k", &v));
+        memory.resize("ns", b"s", Usage::new(b"s", &s));
+        assert_eq!(memory.used(), entry_size(b"k", &v) + entry_size(b"s", &s));
+        assert_eq!(memory.elements(), 2);
+        assert_eq!(memory.keys(ValType::Var), 1);
+        assert_eq!(memory.keys(ValType::Set), 1);
+
+        // Grows the set by a member; the usage matches measuring it again.
+        let grown: Val = set(&[b"a", b"bb", b"ccc"]);
+        memory.grow("ns", b"s", &grown, member_size(b"ccc") as isize);
+        assert_eq!(
+            memory.used(),
+            entry_size(b"k", &v) + entry_size(b"s", &grown)
+        );
+        assert_eq!(memory.elements(), 3);
+
+        // Replaces the var by a set under the same key.
+        memory.resize("", b"k", Usage::new(b"k", &s));
+        assert_eq!(memory.keys(ValType::Var), 0);
+        assert_eq!(memory.keys(ValType::Set), 2);
+
+        memory.remove("", b"k");
+        memory.remove("", b"k");
+        assert_eq!(memory.used(), entry_size(b"s", &grown));
+        memory.remove_ns("ns");
+        assert_eq!(memory.used(), 0);
+        assert_eq!(memory.elements(), 0);
+        assert!(memory.spaces.is_empty());
+    }
+
+    #[test]
+    fn remove_repairs_the_moved_index() {
+        let mut memory = Memory::default();
+        let v: Val = var();
+        let keys: Vec<Vec<u8>> = (0..8u8).map(|i| vec![b'k', i]).collect();
+        for key in &keys {
+            memory.resize("", key, Usage::new(key, &v));
+        }
+        // Removes the first, the last and the middle keys; the last key moves each time.
+        for i in [0, 7, 3, 1] {
+            memory.remove("", &keys[i]);
+            check_indices(&memory);
+        }
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..32 {
+            let key: &[u8] = memory.random_key("", &mut rng).unwrap();
+            assert!([2, 4, 5, 6].iter().any(|i| keys[*i] == key));
+        }
+        assert!(memory.random_key("other", &mut rng).is_none());
+    }
+
+    #[test]
+    fn victim_by_policy() {
+        let mut memory = Memory::default();
+        let v: Val = var();
+        for key in [b"a", b"b", b"c"] {
+            memory.resize("", key, Usage::new(key, &v));
+        }
+        memory.resize("ns", b"d", Usage::new(b"d", &v));
+        memory.touch("", b"a");
+        memory.touch("", b"a");
+        memory.touch("", b"c");
+        memory.touch("ns", b"d");
+        let mut rng = StdRng::seed_from_u64(7);
+
+        // Enough samples to see every key.
+        let lru: Option<Slot> = memory.victim(Eviction::AllKeysLru, 64, &mut rng);
+        assert_eq!(lru, Some((String::new(), b"b".to_vec())));
+        // The keys b and c are hit as often; b is older.
+        memory.touch("", b"c");
+        let lfu: Option<Slot> = memory.victim(Eviction::AllKeysLfu, 64, &mut rng);
+        assert_eq!(lfu, Some((String::new(), b"b".to_vec())));
+        memory.remove("", b"b");
+        let lfu: Option<Slot> = memory.victim(Eviction::AllKeysLfu, 64, &mut rng);
+        assert_eq!(lfu, Some(("ns".into(), b"d".to_vec())));
+
+        assert_eq!(memory.victim(Eviction::NoEviction, 64, &mut rng), None);
+        assert_eq!(
+            Memory::default().victim(Eviction::AllKeysLru, 64, &mut rng),
+            None
+        );
+    }
+
+    #[test]
+    fn eviction_names() {
+        for policy in [
+            Eviction::NoEviction,
+            Eviction::AllKeysLru,
+            Eviction::AllKeysLfu,
+        ] {
+            assert_eq!(policy.as_str().parse::<Eviction>().unwrap(), policy);
+        }
+        assert!("volatile-ttl".parse::<Eviction>().is_err());
+        assert!("lru".parse::<Eviction>().is_err());
+    }
+}
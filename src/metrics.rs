@@ -0,0 +1,221 @@
+use core::convert::Infallible;
+use core::future::Future;
+use core::net::SocketAddr;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::warn;
+
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, StatusCode};
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder, TEXT_FORMAT,
+};
+
+use tonic::Status;
+
+use crate::chan::btree::db::Stats;
+use crate::chan::btree::svc::ChanSvc;
+
+pub const METRICS_PATH: &str = "/metrics";
+
+fn metric_err(e: prometheus::Error) -> Status {
+    Status::internal(format!("unable to register the metric: {e}"))
+}
+
+/// The metrics of the requests and the data; the data gauges are refreshed on scraping.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    queue_depth: IntGauge,
+    keys: IntGaugeVec,
+    memory: IntGauge,
+    evicted: IntCounter,
+    expired: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, Status> {
+        let requests = IntCounterVec::new(
+            Opts::new("memdatabase_requests_total", "The requests handled."),
+            &["command", "code"],
+        )
+        .map_err(metric_err)?;
+        let latency = HistogramVec::new(
+            HistogramOpts::new(
+                "memdatabase_request_duration_seconds",
+                "The time to queue and handle the requests.",
+            ),
+            &["command"],
+        )
+        .map_err(metric_err)?;
+        let queue_depth = IntGauge::new(
+            "memdatabase_queue_depth",
+            "The requests queued to the actor.",
+        )
+        .map_err(metric_err)?;
+        let keys = IntGaugeVec::new(
+            Opts::new("memdatabase_keys", "The keys by the type."),
+            &["type"],
+        )
+        .map_err(metric_err)?;
+        let memory = IntGauge::new(
+            "memdatabase_memory_bytes",
+            "The approximate bytes of the keys and the values.",
+        )
+        .map_err(metric_err)?;
+        let evicted = IntCounter::new("memdatabase_evicted_keys_total", "The keys evicted.")
+            .map_err(metric_err)?;
+        let expired = IntCounter::new(
+            "memdatabase_expired_leases_total",
+            "The lock leases expired.",
+        )
+        .map_err(metric_err)?;
+
+        let registry = Registry::new();
+        registry
+            .register(Box::new(requests.clone()))
+            .map_err(metric_err)?;
+        registry
+            .register(Box::new(latency.clone()))
+            .map_err(metric_err)?;
+        registry
+            .register(Box::new(queue_depth.clone()))
+            .map_err(metric_err)?;
+        registry
+            .register(Box::new(keys.clone()))
+            .map_err(metric_err)?;
+        registry
+            .register(Box::new(memory.clone()))
+            .map_err(metric_err)?;
+        registry
+            .register(Box::new(evicted.clone()))
+            .map_err(metric_err)?;
+        registry
+            .register(Box::new(expired.clone()))
+            .map_err(metric_err)?;
+        Ok(Self {
+            registry,
+            requests,
+            latency,
+            queue_depth,
+            keys,
+            memory,
+            evicted,
+            expired,
+        })
+    }
+
+    /// Counts the request without the latency; used by the streams which have no single latency.
+    pub fn count<T>(&self, command: &str, rslt: &Result<T, Status>) {
+        let code: String = match rslt {
+            Ok(_) => "Ok".into(),
+            Err(e) => format!("{:?}", e.code()),
+        };
+        self.requests
+            .with_label_values(&[command, code.as_str()])
+            .inc();
+    }
+
+    pub fn observe<T>(&self, command: &str, rslt: &Result<T, Status>, elapsed: Duration) {
+        self.count(command, rslt);
+        self.time(command, elapsed);
+    }
+
+    /// Observes the latency without counting; used by the chunks of the streams counted once.
+    pub fn time(&self, command: &str, elapsed: Duration) {
+        self.latency
+            .with_label_values(&[command])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn refresh(&self, stats: &Stats, queue_depth: usize) {
+        self.queue_depth.set(queue_depth as i64);
+        self.keys.with_label_values(&["var"]).set(stats.vars as i64);
+        self.keys.with_label_values(&["map"]).set(stats.maps as i64);
+        self.keys.with_label_values(&["set"]).set(stats.sets as i64);
+        self.keys.with_label_values(&["deq"]).set(stats.deqs as i64);
+        self.memory.set(stats.memory as i64);
+        // The counters are kept by the actor; adds the increase since the last scrape.
+        self.evicted
+            .inc_by(stats.evicted.saturating_sub(self.evicted.get()));
+        self.expired
+            .inc_by(stats.expired.saturating_sub(self.expired.get()));
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, Status> {
+        let mut buf: Vec<u8> = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .map_err(|e| Status::internal(format!("unable to encode the metrics: {e}")))?;
+        Ok(buf)
+    }
+}
+
+async fn scrape(metrics: &Metrics, svc: &ChanSvc) -> Result<Vec<u8>, Status> {
+    let stats: Stats = svc.stats().await?;
+    metrics.refresh(&stats, svc.queue_depth());
+    metrics.encode()
+}
+
+fn status_response(code: StatusCode) -> Response<Body> {
+    let mut res: Response<Body> = Response::new(Body::empty());
+    *res.status_mut() = code;
+    res
+}
+
+async fn metrics_handle(
+    req: Request<Body>,
+    metrics: Arc<Metrics>,
+    svc: ChanSvc,
+) -> Result<Response<Body>, Infallible> {
+    let found: bool = req.method() == Method::GET && req.uri().path() == METRICS_PATH;
+    if !found {
+        return Ok(status_response(StatusCode::NOT_FOUND));
+    }
+    let res: Response<Body> = match scrape(&metrics, &svc).await {
+        Ok(buf) => {
+            let mut res: Response<Body> = Response::new(Body::from(buf));
+            res.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static(TEXT_FORMAT));
+            res
+        }
+        Err(e) => {
+            warn!("unable to scrape: {e}");
+            status_response(StatusCode::SERVICE_UNAVAILABLE)
+        }
+    };
+    Ok(res)
+}
+
+/// Serves the metrics over http until the shutdown completes.
+pub async fn metrics_serve<F>(
+    addr: SocketAddr,
+    metrics: Arc<Metrics>,
+    svc: ChanSvc,
+    shutdown: F,
+) -> Result<(), Status>
+where
+    F: Future<Output = ()>,
+{
+    let make = make_service_fn(move |_| {
+        let metrics: Arc<Metrics> = metrics.clone();
+        let svc: ChanSvc = svc.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                metrics_handle(req, metrics.clone(), svc.clone())
+            }))
+        }
+    });
+    hyper::Server::try_bind(&addr)
+        .map_err(|e| Status::internal(format!("unable to listen: {e}")))?
+        .serve(make)
+        .with_graceful_shutdown(shutdown)
+        .await
+        .map_err(|e| Status::internal(format!("unable to serve the metrics: {e}")))
+}
@@ -0,0 +1,271 @@
+use core::ops::Bound;
+
+use tonic::Status;
+
+use crate::memdatabase::v1::scan_request::Pattern as IPattern;
+
+pub enum Token {
+    Lit(u8),
+    /// Matches any single byte(`?`).
+    Any,
+    /// Matches any bytes including none(`*`).
+    Star,
+    /// Matches a byte in(or not in if negated) the ranges(`[a-z]`, `[!a-z]`).
+    Class(bool, Vec<(u8, u8)>),
+}
+
+impl Token {
+    pub fn is_match(&self, b: u8) -> bool {
+        match self {
+            Self::Lit(l) => *l == b,
+            Self::Any => true,
+            Self::Star => true,
+            Self::Class(negated, ranges) => {
+                let found: bool = ranges.iter().any(|(lo, hi)| *lo <= b && b <= *hi);
+                found != *negated
+            }
+        }
+    }
+}
+
+/// The glob pattern over bytes; `\` escapes the next byte.
+pub struct Glob {
+    tokens: Vec<Token>,
+}
+
+impl Glob {
+    pub fn parse(pattern: &[u8]) -> Result<Self, Status> {
+        let mut tokens: Vec<Token> = vec![];
+        let mut i: usize = 0;
+        while i < pattern.len() {
+            let t: Token = match pattern[i] {
+                b'*' => Token::Star,
+                b'?' => Token::Any,
+                b'\\' => {
+                    i += 1;
+                    let b: u8 = *pattern
+                        .get(i)
+                        .ok_or_else(|| Status::invalid_argument("trailing escape"))?;
+                    Token::Lit(b)
+                }
+                b'[' => {
+                    let (t, end) = Self::parse_class(pattern, i + 1)?;
+                    i = end;
+                    t
+                }
+                b => Token::Lit(b),
+            };
+            tokens.push(t);
+            i += 1;
+        }
+        Ok(Self { tokens })
+    }
+
+    /// Parses the class after `[`; returns the token and the index of `]`.
+    fn parse_class(pattern: &[u8], start: usize) -> Result<(Token, usize), Status> {
+        let unclosed = || Status::invalid_argument("unclosed class");
+        let mut i: usize = start;
+        let negated: bool = matches!(pattern.get(i), Some(b'!') | Some(b'^'));
+        if negated {
+            i += 1;
+        }
+        let mut ranges: Vec<(u8, u8)> = vec![];
+        loop {
+            let b: u8 = *pattern.get(i).ok_or_else(unclosed)?;
+            let lo: u8 = match b {
+                b']' if i > start + usize::from(negated) => {
+                    return Ok((Token::Class(negated, ranges), i))
+                }
+                b'\\' => {
+                    i += 1;
+                    *pattern.get(i).ok_or_else(unclosed)?
+                }
+                b => b,
+            };
+            let hi: u8 = match (pattern.get(i + 1), pattern.get(i + 2)) {
+                (Some(b'-'), Some(&h)) if h != b']' => {
+                    i += 2;
+                    h
+                }
+                _ => lo,
+            };
+            ranges.push((lo.min(hi), lo.max(hi)));
+            i += 1;
+        }
+    }
+
+    /// Gets the bytes which every matching key starts with.
+    pub fn prefix(&self) -> Vec<u8> {
+        self.tokens
+            .iter()
+            .map_while(|t| match t {
+                Token::Lit(b) => Some(*b),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn is_match(&self, key: &[u8]) -> bool {
+        let tokens: &[Token] = &self.tokens;
+        let mut t: usize = 0;
+        let mut k: usize = 0;
+
+        // The position after the last star and the key position it currently covers.
+        let mut backtrack: Option<(usize, usize)> = None;
+
+        while k < key.len() {
+            match tokens.get(t) {
+                Some(Token::Star) => {
+                    t += 1;
+                    backtrack = Some((t, k));
+                }
+                Some(token) if token.is_match(key[k]) => {
+                    t += 1;
+                    k += 1;
+                }
+                _ => match backtrack {
+                    None => return false,
+                    Some((bt, bk)) => {
+                        t = bt;
+                        k = bk + 1;
+                        backtrack = Some((bt, bk + 1));
+                    }
+                },
+            }
+        }
+        tokens[t.min(tokens.len())..]
+            .iter()
+            .all(|token| matches!(token, Token::Star))
+    }
+}
+
+pub enum Pattern {
+    Prefix(Vec<u8>),
+    Glob(Glob),
+}
+
+impl Pattern {
+    pub fn prefix(&self) -> Vec<u8> {
+        match self {
+            Self::Prefix(p) => p.clone(),
+            Self::Glob(g) => g.prefix(),
+        }
+    }
+
+    pub fn is_match(&self, key: &[u8]) -> bool {
+        match self {
+            Self::Prefix(p) => key.starts_with(p),
+            Self::Glob(g) => g.is_match(key),
+        }
+    }
+
+    /// Gets the range of the keys which may match, resuming after the key if any.
+    pub fn range(&self, after: Option<Vec<u8>>) -> (Bound<Vec<u8>>, Bound<Vec<u8>>) {
+        let prefix: Vec<u8> = self.prefix();
+        let upper: Bound<Vec<u8>> = prefix_end(&prefix);
+        let lower: Bound<Vec<u8>> = match after {
+            Some(key) if prefix <= key => Bound::Excluded(key),
+            _ => Bound::Included(prefix),
+        };
+        (lower, upper)
+    }
+}
+
+impl TryFrom<Option<IPattern>> for Pattern {
+    type Error = Status;
+
+    fn try_from(op: Option<IPattern>) -> Result<Self, Self::Error> {
+        let p: IPattern = op.ok_or_else(|| Status::invalid_argument("no pattern specified"))?;
+        match p {
+            IPattern::Prefix(prefix) => Ok(Self::Prefix(prefix)),
+            IPattern::Glob(glob) => Glob::parse(&glob).map(Self::Glob),
+        }
+    }
+}
+
+/// Gets the upper bound(excluded) of the keys which start with the prefix.
+pub fn prefix_end(prefix: &[u8]) -> Bound<Vec<u8>> {
+    let mut end: Vec<u8> = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Bound::Excluded(end);
+        }
+    }
+    Bound::Unbounded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str) -> Glob {
+        Glob::parse(pattern.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn glob_backtracks() {
+        let g: Glob = glob("a*b*c");
+        assert!(g.is_match(b"abc"));
+        assert!(g.is_match(b"aXbXbXc"));
+        assert!(g.is_match(b"abbbcbc"));
+        assert!(!g.is_match(b"abcb"));
+        assert!(!g.is_match(b"acb"));
+
+        let g: Glob = glob("*ab");
+        assert!(g.is_match(b"aab"));
+        assert!(g.is_match(b"abab"));
+        assert!(!g.is_match(b"aba"));
+
+        assert!(glob("**").is_match(b""));
+        assert!(glob("*").is_match(b"any"));
+        assert!(!glob("?").is_match(b""));
+        assert!(glob("").is_match(b""));
+        assert!(!glob("").is_match(b"a"));
+    }
+
+    #[test]
+    fn glob_classes_and_escapes() {
+        let g: Glob = glob("k[a-c]?");
+        assert!(g.is_match(b"kbz"));
+        assert!(!g.is_match(b"kdz"));
+        assert!(glob("k[!a-c]").is_match(b"kd"));
+        assert!(!glob("k[!a-c]").is_match(b"ka"));
+        assert!(glob("[]]").is_match(b"]"));
+        assert!(glob(r"a\*").is_match(b"a*"));
+        assert!(!glob(r"a\*").is_match(b"ab"));
+        assert!(Glob::parse(b"a\\").is_err());
+        assert!(Glob::parse(b"[ab").is_err());
+    }
+
+    #[test]
+    fn glob_prefix() {
+        assert_eq!(glob(r"ab\*c*").prefix(), b"ab*c");
+        assert_eq!(glob("?ab").prefix(), b"");
+    }
+
+    #[test]
+    fn prefix_end_bounds() {
+        assert_eq!(prefix_end(b"ab"), Bound::Excluded(b"ac".to_vec()));
+        assert_eq!(prefix_end(b"a\xff"), Bound::Excluded(b"b".to_vec()));
+        assert_eq!(prefix_end(b"a\xff\xff"), Bound::Excluded(b"b".to_vec()));
+        assert_eq!(prefix_end(b"\xff\xff"), Bound::Unbounded);
+        assert_eq!(prefix_end(b""), Bound::Unbounded);
+    }
+
+    #[test]
+    fn range_resumes_within_prefix() {
+        let p = Pattern::Prefix(b"k".to_vec());
+        let (lower, upper) = p.range(None);
+        assert_eq!(lower, Bound::Included(b"k".to_vec()));
+        assert_eq!(upper, Bound::Excluded(b"l".to_vec()));
+        let (lower, _) = p.range(Some(b"k1".to_vec()));
+        assert_eq!(lower, Bound::Excluded(b"k1".to_vec()));
+        let (lower, _) = p.range(Some(b"a".to_vec()));
+        assert_eq!(lower, Bound::Included(b"k".to_vec()));
+
+        let (lower, upper) = Pattern::Glob(glob("*")).range(None);
+        assert_eq!(lower, Bound::Included(vec![]));
+        assert_eq!(upper, Bound::Unbounded);
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+/// Logs the commands handled slower than this.
+pub const SLOWLOG_THRESHOLD_DEFAULT: Duration = Duration::from_millis(10);
+
+/// The number of the entries kept by default.
+pub const SLOWLOG_MAX_LEN_DEFAULT: usize = 128;
+
+/// The bytes of the key kept; the longer key is truncated.
+pub const SLOWLOG_KEY_MAX: usize = 128;
+
+/// The command handled slower than the threshold.
+pub struct SlowEntry {
+    pub id: u64,
+    pub start: SystemTime,
+    pub duration: Duration,
+    pub command: &'static str,
+    pub ns: String,
+    /// The first key or the lower bound of the range truncated; empty if none.
+    pub key: Vec<u8>,
+    /// The bytes of the keys and the values; see Req::arg_bytes.
+    pub arg_bytes: usize,
+}
+
+/// Keeps the newest entries; the oldest entry is dropped if full.
+#[derive(Default)]
+pub struct SlowLog {
+    entries: VecDeque<SlowEntry>,
+    next_id: u64,
+}
+
+impl SlowLog {
+    /// Adds the entry numbered by the log; nothing is kept if the max length is zero.
+    pub fn push(&mut self, mut entry: SlowEntry, max_len: usize) {
+        entry.id = self.next_id;
+        self.next_id += 1;
+        self.entries.push_front(entry);
+        self.entries.truncate(max_len);
+    }
+
+    /// Gets the newest entries; all entries if the count is zero.
+    pub fn newest(&self, count: usize) -> impl Iterator<Item = &SlowEntry> {
+        let count: usize = match count {
+            0 => self.entries.len(),
+            _ => count,
+        };
+        self.entries.iter().take(count)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Removes all entries; returns the number of the entries removed.
+    pub fn reset(&mut self) -> usize {
+        let sz: usize = self.entries.len();
+        self.entries.clear();
+        sz
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &'static str) -> SlowEntry {
+        SlowEntry {
+            id: 0,
+            start: SystemTime::UNIX_EPOCH,
+            duration: Duration::ZERO,
+            command,
+            ns: String::new(),
+            key: vec![],
+            arg_bytes: 0,
+        }
+    }
+
+    fn commands(log: &SlowLog, count: usize) -> Vec<&'static str> {
+        log.newest(count).map(|e| e.command).collect()
+    }
+
+    #[test]
+    fn push_keeps_the_newest() {
+        let mut log = SlowLog::default();
+        for command in ["Get", "Set", "Del", "Push"] {
+            log.push(entry(command), 3);
+        }
+        assert_eq!(log.len(), 3);
+        assert_eq!(commands(&log, 0), ["Push", "Del", "Set"]);
+        assert_eq!(commands(&log, 2), ["Push", "Del"]);
+        assert_eq!(commands(&log, 10), ["Push", "Del", "Set"]);
+        let ids: Vec<u64> = log.newest(0).map(|e| e.id).collect();
+        assert_eq!(ids, [3, 2, 1]);
+    }
+
+    #[test]
+    fn ids_kept_across_resets() {
+        let mut log = SlowLog::default();
+        log.push(entry("Get"), 3);
+        log.push(entry("Set"), 3);
+        assert_eq!(log.reset(), 2);
+        assert!(log.is_empty());
+        log.push(entry("Del"), 3);
+        assert_eq!(log.newest(0).map(|e| e.id).collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn push_nothing_if_disabled() {
+        let mut log = SlowLog::default();
+        log.push(entry("Get"), 0);
+        assert!(log.is_empty());
+        assert_eq!(commands(&log, 0), Vec::<&str>::new());
+    }
+}
@@ -0,0 +1,180 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use tracing::{info, warn};
+
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::Sender;
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use tonic::Status;
+
+pub const RELOAD_INTERVAL_DEFAULT: Duration = Duration::from_secs(10);
+
+/// The connections accepted but not yet served.
+pub const ACCEPT_BACKLOG_DEFAULT: usize = 64;
+
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long to wait after failing to accept(e.g. too many open files).
+pub const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// The PEM files of the server certificate and the CA to verify the clients.
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Requires the client certificates signed by the CA if any.
+    pub client_ca: Option<PathBuf>,
+}
+
+fn reader_new(path: &PathBuf) -> Result<BufReader<File>, Status> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| Status::invalid_argument(format!("unable to open {}: {e}", path.display())))
+}
+
+fn certs_load(path: &PathBuf) -> Result<Vec<CertificateDer<'static>>, Status> {
+    let mut rdr: BufReader<File> = reader_new(path)?;
+    rustls_pemfile::certs(&mut rdr)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| Status::invalid_argument(format!("invalid certificate: {e}")))
+}
+
+fn key_load(path: &PathBuf) -> Result<PrivateKeyDer<'static>, Status> {
+    let mut rdr: BufReader<File> = reader_new(path)?;
+    rustls_pemfile::private_key(&mut rdr)
+        .map_err(|e| Status::invalid_argument(format!("invalid key: {e}")))?
+        .ok_or_else(|| Status::invalid_argument("no key found"))
+}
+
+impl TlsFiles {
+    pub fn load(&self) -> Result<ServerConfig, Status> {
+        let certs: Vec<CertificateDer<'static>> = certs_load(&self.cert)?;
+        let key: PrivateKeyDer<'static> = key_load(&self.key)?;
+        let builder = ServerConfig::builder();
+        let builder = match &self.client_ca {
+            None => builder.with_no_client_auth(),
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                for cert in certs_load(ca)? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| Status::invalid_argument(format!("invalid ca: {e}")))?;
+                }
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                    .build()
+                    .map_err(|e| Status::invalid_argument(format!("invalid ca: {e}")))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        let mut config: ServerConfig = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| Status::invalid_argument(format!("invalid certificate: {e}")))?;
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        Ok(config)
+    }
+
+    /// Gets the modified times of the files to detect the rotation.
+    pub fn modified(&self) -> Vec<Option<SystemTime>> {
+        let paths = [Some(&self.cert), Some(&self.key), self.client_ca.as_ref()];
+        paths
+            .into_iter()
+            .flatten()
+            .map(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+/// Keeps the server config loaded from the files; the new connections use the latest one.
+#[derive(Clone)]
+pub struct Reloader {
+    files: Arc<TlsFiles>,
+    config: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+impl Reloader {
+    pub fn new(files: TlsFiles) -> Result<Self, Status> {
+        let config: ServerConfig = files.load()?;
+        Ok(Self {
+            files: Arc::new(files),
+            config: Arc::new(RwLock::new(Arc::new(config))),
+        })
+    }
+
+    pub fn current(&self) -> Arc<ServerConfig> {
+        match self.config.read() {
+            Ok(guard) => guard.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// Reloads the files when modified; keeps the current config if the new files are invalid.
+    pub async fn watch(self, interval: Duration) {
+        let mut last: Vec<Option<SystemTime>> = self.files.modified();
+        loop {
+            tokio::time::sleep(interval).await;
+            let modified: Vec<Option<SystemTime>> = self.files.modified();
+            if modified == last {
+                continue;
+            }
+            match self.files.load() {
+                Ok(config) => {
+                    match self.config.write() {
+                        Ok(mut guard) => *guard = Arc::new(config),
+                        Err(poisoned) => *poisoned.into_inner() = Arc::new(config),
+                    }
+                    info!("the certificates reloaded");
+                    last = modified;
+                }
+                Err(e) => warn!("unable to reload the certificates: {e}"),
+            }
+        }
+    }
+}
+
+/// Accepts the connections and completes the handshakes without blocking the following accepts.
+pub fn incoming(
+    listener: TcpListener,
+    reloader: Reloader,
+) -> ReceiverStream<Result<TlsStream<TcpStream>, std::io::Error>> {
+    let (tx, rx) = tokio::sync::mpsc::channel(ACCEPT_BACKLOG_DEFAULT);
+    tokio::spawn(async move {
+        loop {
+            let (tcp, addr) = match listener.accept().await {
+                Ok(pair) => pair,
+                Err(e) => {
+                    warn!("unable to accept: {e}");
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+            if tx.is_closed() {
+                return;
+            }
+            let acceptor = TlsAcceptor::from(reloader.current());
+            let conn: Sender<_> = tx.clone();
+            tokio::spawn(async move {
+                let handshake = tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(tcp));
+                match handshake.await {
+                    Ok(Ok(tls)) => match conn.send(Ok(tls)).await {
+                        Ok(_) => {}
+                        Err(e) => warn!("the server gone: {e}"),
+                    },
+                    Ok(Err(e)) => warn!("handshake failed with {addr}: {e}"),
+                    Err(_) => warn!("handshake timed out with {addr}"),
+                }
+            });
+        }
+    });
+    ReceiverStream::new(rx)
+}
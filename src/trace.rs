@@ -0,0 +1,86 @@
+use std::io::IsTerminal;
+
+use opentelemetry::global;
+use opentelemetry::propagation::Extractor;
+use opentelemetry::{Context, KeyValue};
+
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{config, Tracer};
+use opentelemetry_sdk::{runtime, Resource};
+
+use opentelemetry_otlp::WithExportConfig;
+
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+use tonic::metadata::{KeyRef, MetadataMap};
+use tonic::Status;
+
+pub const SERVICE_NAME: &str = "memdatabase";
+
+/// The filter used if RUST_LOG is not set.
+pub const FILTER_DEFAULT: &str = "info";
+
+/// Gets the ascii values of the metadata for the propagator.
+pub struct MetadataExtractor<'a>(pub &'a MetadataMap);
+
+impl<'a> Extractor for MetadataExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0
+            .keys()
+            .filter_map(|k| match k {
+                KeyRef::Ascii(k) => Some(k.as_str()),
+                KeyRef::Binary(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Gets the parent context from the W3C `traceparent` and `tracestate` metadata.
+pub fn context_get(meta: &MetadataMap) -> Context {
+    global::get_text_map_propagator(|p| p.extract(&MetadataExtractor(meta)))
+}
+
+fn tracer_new(endpoint: String) -> Result<Tracer, Status> {
+    let exporter = opentelemetry_otlp::new_exporter()
+        .tonic()
+        .with_endpoint(endpoint);
+    let resource = Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)]);
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(exporter)
+        .with_trace_config(config().with_resource(resource))
+        .install_batch(runtime::Tokio)
+        .map_err(|e| Status::invalid_argument(format!("unable to create the exporter: {e}")))
+}
+
+/// Installs the subscriber writing to stderr; exports the spans over OTLP if the endpoint set.
+pub fn tracing_init(otlp_endpoint: Option<String>) -> Result<(), Status> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    let filter: EnvFilter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(FILTER_DEFAULT));
+    let otel = match otlp_endpoint {
+        None => None,
+        Some(endpoint) => Some(tracing_opentelemetry::layer().with_tracer(tracer_new(endpoint)?)),
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_ansi(std::io::stderr().is_terminal()),
+        )
+        .with(otel)
+        .try_init()
+        .map_err(|e| Status::internal(format!("unable to install the subscriber: {e}")))
+}
+
+/// Exports the spans not yet sent; blocks until done.
+pub fn tracing_shutdown() {
+    global::shutdown_tracer_provider()
+}
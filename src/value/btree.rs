@@ -2,6 +2,10 @@ use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 use prost_types::Value;
 
+use crate::memdatabase::v1::val::Val as IVal;
+use crate::memdatabase::v1::Val as PVal;
+use crate::memdatabase::v1::{DeqVal, MapEntry, MapVal, SetVal, ValType};
+
 #[derive(Clone)]
 pub enum Val {
     Var(Value),
@@ -9,3 +13,57 @@ pub enum Val {
     Set(BTreeSet<Vec<u8>>),
     Deq(VecDeque<Value>),
 }
+
+impl Val {
+    pub fn val_type(&self) -> ValType {
+        match self {
+            Self::Var(_) => ValType::Var,
+            Self::Map(_) => ValType::Map,
+            Self::Set(_) => ValType::Set,
+            Self::Deq(_) => ValType::Deq,
+        }
+    }
+
+    /// Gets the number of elements of the collection; zero for the var.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Var(_) => 0,
+            Self::Map(m) => m.len(),
+            Self::Set(s) => s.len(),
+            Self::Deq(q) => q.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks the type; the unspecified type matches any type.
+    pub fn is_type(&self, t: ValType) -> bool {
+        t == ValType::Unspecified || t == self.val_type()
+    }
+}
+
+impl From<&Val> for PVal {
+    fn from(v: &Val) -> Self {
+        let i: IVal = match v {
+            Val::Var(s) => IVal::Var(s.clone()),
+            Val::Map(m) => IVal::Map(MapVal {
+                entries: m
+                    .iter()
+                    .map(|(key, value)| MapEntry {
+                        key: key.clone(),
+                        value: Some(value.clone()),
+                    })
+                    .collect(),
+            }),
+            Val::Set(s) => IVal::Set(SetVal {
+                members: s.iter().cloned().collect(),
+            }),
+            Val::Deq(q) => IVal::Deq(DeqVal {
+                items: q.iter().cloned().collect(),
+            }),
+        };
+        Self { val: Some(i) }
+    }
+}
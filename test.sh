@@ -4,8 +4,10 @@ which grpcurl | fgrep -q grpcurl || exec sh -c 'echo grpcurl missing.; exit 1'
 which jaq | fgrep -q jaq || exec sh -c 'echo jaq missing.; exit 1'
 which base64 | fgrep -q base64 || exec sh -c 'echo base64 missing.; exit 1'
 
-protodir=memdatabase-proto
 server=localhost:50051
+token="${ENV_AUTH_TOKEN:-}"
+socket="${ENV_UNIX_SOCKET:-}"
+metrics_addr="${ENV_METRICS_ADDR:-}"
 
 varset() {
 
@@ -15,8 +17,6 @@ varset() {
 		-n '{ key: $key, value: "helo" }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Set
@@ -27,8 +27,6 @@ varset() {
 		-n '{ key: $key, value: "helo" }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Set
@@ -39,8 +37,6 @@ varset() {
 		-n '{ key: $key, value: 3 }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Set
@@ -55,8 +51,6 @@ varget() {
 		-n '{ key: $key }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Get
@@ -74,8 +68,6 @@ range() {
     }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Range
@@ -89,8 +81,6 @@ varpush() {
 		-n '{ key: $key, value: "wwww", front: true }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Push
@@ -105,8 +95,6 @@ varpopback() {
 		-n '{ key: $key, front: false }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Pop
@@ -121,8 +109,6 @@ varpopfront() {
 		-n '{ key: $key, front: true }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Pop
@@ -137,8 +123,6 @@ qlen() {
 		-n '{ key: $key }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/QLen
@@ -154,8 +138,6 @@ dictset() {
 		-n '{ key: $key, dkey: $dkey, value: 3776.0 }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/DSet
@@ -171,8 +153,6 @@ dictget() {
 		-n '{ key: $key, dkey: $dkey }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/DGet
@@ -188,8 +168,6 @@ dictfind() {
 		-n '{ key: $key, dkey: $dkey }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/DHas
@@ -205,8 +183,6 @@ set_add() {
 		-n '{ key: $key, val: $val }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/SAdd
@@ -222,8 +198,6 @@ set_del() {
 		-n '{ key: $key, val: $val }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/SDel
@@ -239,8 +213,6 @@ set_len() {
 		-n '{ key: $key }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/SLen
@@ -255,14 +227,605 @@ del_key() {
 		-n '{ key: $key }' |
 		grpcurl \
 			-plaintext \
-			-import-path "${protodir}" \
-			-proto memdatabase/v1/svc.proto \
 			-d @ \
 			"${server}" \
 			memdatabase.v1.MemoryDatabaseService/Del
 
 }
 
+execute() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n queue4567 | base64)" \
+		-n '
+      { id: 1, push: { key: $key, value: "wwww" } },
+      { id: 2, push: { key: $key, value: "xxxx" } },
+      { id: 3, q_len: { key: $key } },
+      { id: 4, pop: { key: $key, front: true } }
+    ' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Execute
+
+}
+
+counter() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n counter0123 | base64)" \
+		-n '{ key: $key }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Incr
+
+	jaq \
+		-c \
+		--arg key "$(echo -n counter0123 | base64)" \
+		-n '{ key: $key, delta: -3 }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/IncrBy
+
+	jaq \
+		-c \
+		--arg key "$(echo -n counter0123 | base64)" \
+		-n '{ key: $key, delta: 0.5 }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/IncrByFloat
+
+	jaq \
+		-c \
+		--arg key "$(echo -n counter0123 | base64)" \
+		-n '{ key: $key, delta: "9007199254740993" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/IncrBy 2>&1 |
+		fgrep -q OutOfRange ||
+		exit 1
+
+}
+
+dict_counter() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n dict4567 | base64)" \
+		--arg dkey "$(echo -n hits | base64)" \
+		-n '{ key: $key, dkey: $dkey, delta: 42 }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/DIncrBy
+
+}
+
+strings() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n log0123 | base64)" \
+		-n '{ key: $key, value: "helo" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Append
+
+	jaq \
+		-c \
+		--arg key "$(echo -n log0123 | base64)" \
+		-n '{ key: $key, offset: 2, value: "LO, wrld" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/SetRange
+
+	jaq \
+		-c \
+		--arg key "$(echo -n log0123 | base64)" \
+		-n '{ key: $key }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/StrLen
+
+	jaq \
+		-c \
+		--arg key "$(echo -n log0123 | base64)" \
+		-n '{ key: $key, start: 0, end: -1 }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/GetRange
+
+}
+
+varset_nx() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n lock0123 | base64)" \
+		-n '{ key: $key, value: "owner0", mode: "SET_MODE_NX" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Set
+
+	jaq \
+		-c \
+		--arg key "$(echo -n lock0123 | base64)" \
+		-n '{ key: $key, value: "owner1", mode: "SET_MODE_XX", get: true }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Set
+
+}
+
+lock() {
+
+	jaq \
+		-c \
+		--arg name "$(echo -n mutex0123 | base64)" \
+		-n '{ name: $name, owner: "worker0", ttl: "10s" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Acquire
+
+	jaq \
+		-c \
+		--arg name "$(echo -n mutex0123 | base64)" \
+		-n '{ name: $name, owner: "worker1", ttl: "10s", wait: "1s" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Acquire
+
+	jaq \
+		-c \
+		--arg name "$(echo -n mutex0123 | base64)" \
+		-n '{ name: $name, owner: "worker0", token: 1, ttl: "10s" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Renew
+
+	jaq \
+		-c \
+		--arg name "$(echo -n mutex0123 | base64)" \
+		-n '{ name: $name, owner: "worker0", token: 1 }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Release
+
+}
+
+range_rev() {
+
+	jaq \
+		-c \
+		--arg lower "$(echo -n 0000 | base64)" \
+		-n '{
+      lower: { included: $lower },
+      upper: { unbounded: {} },
+      limit: 2,
+      reverse: true,
+    }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Range
+
+}
+
+range_values() {
+
+	jaq \
+		-c \
+		-n '{
+      lower: { unbounded: {} },
+      upper: { unbounded: {} },
+      with_values: true,
+      type_filter: "VAL_TYPE_DEQ",
+    }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Range
+
+}
+
+scan() {
+
+	jaq \
+		-c \
+		--arg prefix "$(echo -n queue | base64)" \
+		-n '{ prefix: $prefix }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Scan
+
+	jaq \
+		-c \
+		--arg glob "$(echo -n 'dict*' | base64)" \
+		-n '{ glob: $glob, type_filter: "VAL_TYPE_MAP" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Scan
+
+}
+
+range_del() {
+
+	jaq \
+		-c \
+		--arg lower "$(echo -n counter | base64)" \
+		--arg upper "$(echo -n counter~ | base64)" \
+		-n '{
+      lower: { included: $lower },
+      upper: { excluded: $upper },
+    }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/CountRange
+
+	jaq \
+		-c \
+		--arg lower "$(echo -n counter | base64)" \
+		--arg upper "$(echo -n counter~ | base64)" \
+		-n '{
+      lower: { included: $lower },
+      upper: { excluded: $upper },
+    }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/DelRange
+
+}
+
+keys() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo | base64)" \
+		--arg other "$(echo -n nosuchkey | base64)" \
+		-n '{ keys: [$key, $other] }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Exists
+
+	jaq \
+		-c \
+		--arg key "$(echo -n queue0123 | base64)" \
+		-n '{ key: $key }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Type
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo | base64)" \
+		--arg new_key "$(echo -n helo2 | base64)" \
+		-n '{ key: $key, new_key: $new_key }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Copy
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo2 | base64)" \
+		--arg new_key "$(echo -n helo3 | base64)" \
+		-n '{ key: $key, new_key: $new_key, nx: true }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Rename
+
+}
+
+del_keys() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo | base64)" \
+		--arg other "$(echo -n HELO | base64)" \
+		-n '{ keys: [$key, $other], return_values: true }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Del
+
+}
+
+queue_bulk() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo-q | base64)" \
+		-n '{ key: $key, values: [1, 2, 3] }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Push
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo-q | base64)" \
+		-n '{ key: $key, front: true, count: 2 }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Pop
+
+}
+
+random() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo-s | base64)" \
+		--arg val "$(echo -n wrld | base64)" \
+		-n '{ key: $key, val: $val }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/SAdd
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo-s | base64)" \
+		-n '{ key: $key, count: 3, repeat: true }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/SRandMember
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo-s | base64)" \
+		-n '{ key: $key }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/SPop
+
+	jaq \
+		-c \
+		-n '{}' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/RandomKey
+
+}
+
+namespace() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo | base64)" \
+		-n '{ key: $key, value: 42 }' |
+		grpcurl \
+			-plaintext \
+			-H "x-memdatabase-namespace: team-a" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Set
+
+	jaq \
+		-c \
+		-n '{}' |
+		grpcurl \
+			-plaintext \
+			-H "x-memdatabase-namespace: team-a" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/DbSize
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo | base64)" \
+		-n '{ key: $key, namespace: "team-b" }' |
+		grpcurl \
+			-plaintext \
+			-H "x-memdatabase-namespace: team-a" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Move
+
+	jaq \
+		-c \
+		-n '{}' |
+		grpcurl \
+			-plaintext \
+			-H "x-memdatabase-namespace: team-b" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/FlushDb
+
+}
+
+auth() {
+
+	jaq \
+		-c \
+		--arg key "$(echo -n helo | base64)" \
+		-n '{ key: $key }' |
+		grpcurl \
+			-plaintext \
+			-H "authorization: Bearer ${token}" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Get
+
+}
+
+acl() {
+
+	jaq \
+		-c \
+		-n '{}' |
+		grpcurl \
+			-plaintext \
+			-H "authorization: Bearer ${token}" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/AclWhoAmI
+
+	jaq \
+		-c \
+		-n '{}' |
+		grpcurl \
+			-plaintext \
+			-H "authorization: Bearer ${token}" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/AclList
+
+}
+
+unix() {
+	test -n "${socket}" || return 0
+
+	jaq \
+		-c \
+		-n '{}' |
+		grpcurl \
+			-plaintext \
+			-unix \
+			-d @ \
+			"${socket}" \
+			memdatabase.v1.MemoryDatabaseService/DbSize
+
+}
+
+metrics() {
+	test -n "${metrics_addr}" || return 0
+
+	curl \
+		--silent \
+		--fail \
+		"http://${metrics_addr}/metrics" |
+		grep '^memdatabase_'
+
+}
+
+slowlog() {
+
+	jaq \
+		-c \
+		-n '{ count: 3 }' |
+		grpcurl \
+			-plaintext \
+			-H "authorization: Bearer ${token}" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/SlowLogGet
+
+	jaq \
+		-c \
+		-n '{}' |
+		grpcurl \
+			-plaintext \
+			-H "authorization: Bearer ${token}" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/SlowLogReset
+
+}
+
+health() {
+
+	jaq \
+		-c \
+		-n '{ service: "memdatabase.v1.MemoryDatabaseService" }' |
+		grpcurl \
+			-plaintext \
+			-d @ \
+			"${server}" \
+			grpc.health.v1.Health/Check |
+		jaq -e '.status == "SERVING"' ||
+		exit 1
+
+	grpcurl \
+		-plaintext \
+		"${server}" \
+		list |
+		fgrep -qx memdatabase.v1.MemoryDatabaseService ||
+		exit 1
+
+	grpcurl \
+		-plaintext \
+		"${server}" \
+		describe \
+		memdatabase.v1.MemoryDatabaseService.Get ||
+		exit 1
+
+}
+
+info() {
+
+	jaq \
+		-c \
+		-n '{}' |
+		grpcurl \
+			-plaintext \
+			-H "authorization: Bearer ${token}" \
+			-d @ \
+			"${server}" \
+			memdatabase.v1.MemoryDatabaseService/Info
+
+}
+
 varset
 range
 varget
@@ -277,3 +840,25 @@ set_del
 set_add
 set_len
 del_key
+execute
+counter
+dict_counter
+strings
+varset_nx
+lock
+range_rev
+range_values
+scan
+range_del
+keys
+del_keys
+queue_bulk
+random
+namespace
+auth
+acl
+unix
+metrics
+slowlog
+info
+health
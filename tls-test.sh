@@ -0,0 +1,86 @@
+#!/bin/sh
+
+which openssl | fgrep -q openssl || exec sh -c 'echo openssl missing.; exit 1'
+which grpcurl | fgrep -q grpcurl || exec sh -c 'echo grpcurl missing.; exit 1'
+
+server=localhost:50443
+certdir=$(mktemp -d)
+
+trap 'kill ${pid} 2>/dev/null; rm -rf "${certdir}"' EXIT
+
+# Generates the self-signed CA and the certificate signed by it.
+certgen() {
+	name=$1
+	ext=$2
+
+	openssl req \
+		-x509 \
+		-newkey ec \
+		-pkeyopt ec_paramgen_curve:P-256 \
+		-nodes \
+		-days 1 \
+		-subj "/CN=${name}-ca" \
+		-keyout "${certdir}/${name}-ca.key" \
+		-out "${certdir}/${name}-ca.pem" 2>/dev/null
+
+	openssl req \
+		-newkey ec \
+		-pkeyopt ec_paramgen_curve:P-256 \
+		-nodes \
+		-subj "/CN=${name}" \
+		-keyout "${certdir}/${name}.key" \
+		-out "${certdir}/${name}.csr" 2>/dev/null
+
+	printf '%s\n' "${ext}" >"${certdir}/${name}.ext"
+
+	openssl x509 \
+		-req \
+		-days 1 \
+		-in "${certdir}/${name}.csr" \
+		-CA "${certdir}/${name}-ca.pem" \
+		-CAkey "${certdir}/${name}-ca.key" \
+		-CAcreateserial \
+		-extfile "${certdir}/${name}.ext" \
+		-out "${certdir}/${name}.pem" 2>/dev/null
+}
+
+servergen() {
+	certgen server 'subjectAltName=DNS:localhost
+extendedKeyUsage=serverAuth'
+}
+
+clientgen() {
+	certgen client 'extendedKeyUsage=clientAuth'
+}
+
+dbsize() {
+	grpcurl \
+		-cacert "${certdir}/server-ca.pem" \
+		"$@" \
+		-d '{}' \
+		"${server}" \
+		memdatabase.v1.MemoryDatabaseService/DbSize
+}
+
+servergen
+clientgen
+
+ENV_LISTEN_ADDR=127.0.0.1:50443 \
+	ENV_TLS_CERT="${certdir}/server.pem" \
+	ENV_TLS_KEY="${certdir}/server.key" \
+	ENV_TLS_CLIENT_CA="${certdir}/client-ca.pem" \
+	ENV_TLS_RELOAD_SECONDS=1 \
+	./target/release/memdatabase &
+pid=$!
+sleep 1
+
+echo with the client certificate
+dbsize -cert "${certdir}/client.pem" -key "${certdir}/client.key" || exit 1
+
+echo without the client certificate
+dbsize && exit 1
+
+echo rotated
+servergen
+sleep 3
+dbsize -cert "${certdir}/client.pem" -key "${certdir}/client.key" || exit 1